@@ -0,0 +1,125 @@
+use std::fmt;
+
+use num_bigint::BigInt;
+use serde::{Deserializer, Serializer, de::{self, Visitor}};
+
+/// Name of the newtype struct through which big integers are exchanged with
+/// the `Serializer` and `Deserializer`, as their decimal digits.
+pub(crate) const TOKEN: &str = "$bencode_rs::private::BigInt";
+
+/// Serializes a `BigInt` as a Bencode integer, for use with
+/// `#[serde(with = "bencode_rs::bigint")]`.
+pub fn serialize<S>(value: &BigInt, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_newtype_struct(TOKEN, &value.to_string())
+}
+
+/// Deserializes a Bencode integer of any size into a `BigInt`, for use with
+/// `#[serde(with = "bencode_rs::bigint")]`.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<BigInt, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_newtype_struct(TOKEN, BigIntVisitor)
+}
+
+pub(crate) struct BigIntVisitor;
+
+impl<'de> Visitor<'de> for BigIntVisitor {
+    type Value = BigInt;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an integer")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<BigInt, E> {
+        Ok(BigInt::from(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<BigInt, E> {
+        Ok(BigInt::from(v))
+    }
+
+    fn visit_i128<E: de::Error>(self, v: i128) -> Result<BigInt, E> {
+        Ok(BigInt::from(v))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<BigInt, E> {
+        Ok(BigInt::from(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<BigInt, E> {
+        v.parse().map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<BigInt, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::BigInt;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{from_bytes, from_reader, from_value, to_bytes, to_value, Value};
+
+    const BIG: &[u8] = b"i-123456789012345678901234567890123456789012345678901234567890e";
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Stats {
+        #[serde(with = "crate::bigint")]
+        downloaded: BigInt,
+        #[serde(with = "crate::bigint")]
+        peers: BigInt,
+    }
+
+    fn big() -> BigInt {
+        "-123456789012345678901234567890123456789012345678901234567890".parse().unwrap()
+    }
+
+    #[test]
+    fn test_bigint() {
+        let stats = Stats { downloaded: big(), peers: BigInt::from(3) };
+        let bytes = to_bytes(&stats).unwrap();
+
+        assert_eq!(bytes, [&b"d10:downloaded"[..], BIG, b"5:peersi3ee"].concat());
+        assert_eq!(from_bytes::<Stats>(&bytes).unwrap(), stats);
+        assert_eq!(from_reader::<_, Stats>(&bytes[..]).unwrap(), stats);
+        assert_eq!(from_value::<Stats>(to_value(&stats).unwrap()).unwrap(), stats);
+    }
+
+    #[test]
+    fn test_bigint_value() {
+        let v: Value = from_bytes(BIG).unwrap();
+
+        assert_eq!(v, Value::BigInteger(big()));
+        assert_eq!(to_bytes(&v).unwrap(), BIG);
+        assert_eq!(Value::from(BigInt::from(7)), Value::Integer(7));
+        assert_eq!(to_value(&u64::MAX).unwrap(), Value::BigInteger(BigInt::from(u64::MAX)));
+        assert_eq!(from_value::<u64>(Value::BigInteger(BigInt::from(u64::MAX))).unwrap(), u64::MAX);
+        assert_eq!(from_value::<Value>(v.clone()).unwrap(), v);
+    }
+
+    #[test]
+    fn test_bigint_err() {
+        let a = from_bytes::<i64>(BIG);
+        let b = from_bytes::<Vec<u128>>(b"li1ei340282366920938463463374607431768211456ee");
+        let c = from_value::<i64>(Value::BigInteger(big()));
+        let d = from_bytes::<Stats>(b"d10:downloaded1:15:peersi3ee");
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "integer out of range at index 0");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "integer out of range at index 4 in [1]");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "integer out of range");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "expected integer at index 14 in downloaded");
+    }
+}
@@ -55,7 +55,7 @@ impl<const N: usize> Serialize for [u8; N] {
     }
 }
 
-impl<'a, T> Serialize for &'a T
+impl<T> Serialize for &T
 where
     T: ?Sized + Serialize,
 {
@@ -137,7 +137,7 @@ impl<'de, const N: usize> Deserialize<'de> for [u8; N] {
                 write!(formatter, "an byte array of size {}", N)
             }
 
-            fn visit_borrowed_bytes<E>(self, b: &'de [u8]) -> Result<Self::Value, E>
+            fn visit_bytes<E>(self, b: &[u8]) -> Result<Self::Value, E>
             where
                 E: Error,
             {
@@ -284,7 +284,7 @@ mod tests {
     fn test_ser_bencode_bytes() {
         let t = Test { bytes: b"super test", vec: b"test".to_vec(), id: [48u8; 20]};
 
-        assert_eq!(b"d5:bytes10:super test3:vec4:test2:id20:00000000000000000000e" as &[u8], to_bytes(&t).unwrap());
+        assert_eq!(b"d5:bytes10:super test2:id20:000000000000000000003:vec4:teste" as &[u8], to_bytes(&t).unwrap());
     }
 
     #[test]
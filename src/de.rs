@@ -1,25 +1,268 @@
-
 use std::{
+    cmp::Ordering,
+    io,
+    marker::PhantomData,
     str,
-    ops::{AddAssign, MulAssign}
 };
 
 use num_traits::FromPrimitive;
 use serde::{
     Deserialize,
-    de::{self, Visitor, DeserializeSeed, IntoDeserializer}
+    de::{self, DeserializeOwned, Visitor, DeserializeSeed, IntoDeserializer},
+    forward_to_deserialize_any,
 };
 
-use super::error::{Error, Result, ErrorKind};
+#[cfg(feature = "bigint")]
+use super::bigint;
+use super::error::{Error, Result, ErrorKind, PathSegment};
+use super::raw;
+use super::read::{self, Read, Reference};
+use super::ser::{scale_factor, EnumRepr, FloatPolicy};
+
+pub struct Deserializer<R> {
+    read: R,
+    scratch: Vec<u8>,
+    pub(crate) options: DeserializerOptions,
+    remaining_depth: usize,
+    /// Whether an `Option` read before any container is `None` on empty
+    /// input. Set by the `from_*` functions, which read a whole document.
+    top_level: bool,
+}
+
+/// Settings controlling what input a `Deserializer` accepts.
+///
+/// The limits are checked as the input is consumed, before any work is done
+/// for the offending part: a byte string declaring a length above the limit
+/// is rejected without being read or allocated.
+#[derive(Debug, Clone, Copy)]
+pub struct DeserializerOptions {
+    pub(crate) strict: bool,
+    bool_policy: BoolPolicy,
+    string_policy: StringPolicy,
+    float_policy: FloatPolicy,
+    enum_repr: EnumRepr,
+    pub(crate) recursion_limit: usize,
+    pub(crate) max_bytes_len: usize,
+    pub(crate) max_list_len: usize,
+    pub(crate) max_dict_len: usize,
+    pub(crate) max_input_len: usize,
+}
+
+/// Bencode has no boolean type, booleans are encoded as the integers `0` and
+/// `1`. This decides how other integers are treated when decoding a `bool`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoolPolicy {
+    /// Only `i0e` and `i1e` are accepted.
+    ZeroOrOne,
+    /// `i0e` is `false`, any other integer is `true`.
+    NonZero,
+}
+
+/// Bencode byte strings carry no encoding. This decides how they are handed
+/// to visitors through `deserialize_any`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringPolicy {
+    /// Valid UTF-8 is visited as a string, anything else as bytes.
+    Utf8OrBytes,
+    /// Byte strings are always visited as bytes.
+    Bytes,
+}
+
+impl Default for DeserializerOptions {
+    fn default() -> Self {
+        DeserializerOptions {
+            strict: false,
+            bool_policy: BoolPolicy::ZeroOrOne,
+            string_policy: StringPolicy::Utf8OrBytes,
+            float_policy: FloatPolicy::Reject,
+            enum_repr: EnumRepr::Name,
+            recursion_limit: 128,
+            max_bytes_len: usize::MAX,
+            max_list_len: usize::MAX,
+            max_dict_len: usize::MAX,
+            max_input_len: usize::MAX,
+        }
+    }
+}
+
+impl DeserializerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// In strict mode, only the canonical encoding of a value is accepted:
+    /// dictionary keys must appear sorted as raw byte strings, without
+    /// duplicates. This guarantees that two different inputs never decode to
+    /// the same value, which matters when the input is hashed or signed.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Which integers are accepted when a `bool` is expected.
+    pub fn bool_policy(mut self, policy: BoolPolicy) -> Self {
+        self.bool_policy = policy;
+        self
+    }
+
+    /// How byte strings are visited by self-describing deserialization, as
+    /// used by `#[serde(untagged)]`, `#[serde(flatten)]` or dynamically typed
+    /// targets.
+    pub fn string_policy(mut self, policy: StringPolicy) -> Self {
+        self.string_policy = policy;
+        self
+    }
+
+    /// Which encoding floats are read from, see `FloatPolicy`. Defaults to
+    /// `FloatPolicy::Reject` like `SerializerOptions`, under which floats are
+    /// read from integers.
+    pub fn float_policy(mut self, policy: FloatPolicy) -> Self {
+        self.float_policy = policy;
+        self
+    }
+
+    /// How enum variants are identified, see `EnumRepr`.
+    pub fn enum_repr(mut self, repr: EnumRepr) -> Self {
+        self.enum_repr = repr;
+        self
+    }
+
+    /// How deeply lists, dictionaries and enums may be nested before parsing
+    /// fails with `RecursionLimitExceeded`. Defaults to 128, which keeps
+    /// hostile inputs from overflowing the stack.
+    pub fn recursion_limit(mut self, limit: usize) -> Self {
+        self.recursion_limit = limit;
+        self
+    }
+
+    /// Maximum length of a single byte string.
+    pub fn max_bytes_len(mut self, len: usize) -> Self {
+        self.max_bytes_len = len;
+        self
+    }
+
+    /// Maximum number of elements in a single list.
+    pub fn max_list_len(mut self, len: usize) -> Self {
+        self.max_list_len = len;
+        self
+    }
+
+    /// Maximum number of entries in a single dictionary.
+    pub fn max_dict_len(mut self, len: usize) -> Self {
+        self.max_dict_len = len;
+        self
+    }
+
+    /// Maximum number of bytes consumed from the input.
+    pub fn max_input_len(mut self, len: usize) -> Self {
+        self.max_input_len = len;
+        self
+    }
+
+    pub fn from_bytes<'de, T>(&self, bytes: &'de [u8]) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        let mut de = Deserializer::with_options(bytes, *self);
+        de.top_level = true;
+        let value = T::deserialize(&mut de)?;
+
+        de.end()?;
+
+        Ok(value)
+    }
+
+    /// Like `from_bytes`, but accepts data following the value and returns
+    /// the number of bytes the value spans along with it.
+    pub fn from_bytes_prefix<'de, T>(&self, bytes: &'de [u8]) -> Result<(T, usize)>
+    where
+        T: Deserialize<'de>,
+    {
+        let mut de = Deserializer::with_options(bytes, *self);
+        de.top_level = true;
+        let value = T::deserialize(&mut de)?;
+
+        Ok((value, de.byte_offset()))
+    }
+
+    pub fn from_reader<R, T>(&self, reader: R) -> Result<T>
+    where
+        R: io::Read,
+        T: DeserializeOwned,
+    {
+        let mut de = Deserializer::from_reader_with_options(reader, *self);
+        de.top_level = true;
+        let value = T::deserialize(&mut de)?;
+
+        de.end()?;
+
+        Ok(value)
+    }
+}
+
+impl<'de, R> Deserializer<R>
+where
+    R: Read<'de>,
+{
+    fn with_read(read: R, options: DeserializerOptions) -> Self {
+        Deserializer {
+            read,
+            scratch: Vec::new(),
+            options,
+            remaining_depth: options.recursion_limit,
+            top_level: false,
+        }
+    }
+
+    /// The input source, e.g. an `IoRead` to get the reader back from.
+    pub fn get_ref(&self) -> &R {
+        &self.read
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.read
+    }
+
+    pub fn into_inner(self) -> R {
+        self.read
+    }
+
+    /// See `DeserializerOptions::strict`.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.options.strict = strict;
+    }
 
-pub struct Deserializer<'de> {
-    input: &'de [u8],
-    index: usize,
+    /// See `DeserializerOptions::recursion_limit`.
+    pub fn set_recursion_limit(&mut self, limit: usize) {
+        self.options.recursion_limit = limit;
+        self.remaining_depth = limit;
+    }
 }
 
-impl<'de> Deserializer<'de> {
-    pub fn new(input: &'de [u8]) -> Self {
-        Deserializer { input, index: 0 }
+impl<'a> Deserializer<read::SliceRead<'a>> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Deserializer::with_options(input, DeserializerOptions::default())
+    }
+
+    pub fn with_options(input: &'a [u8], options: DeserializerOptions) -> Self {
+        Deserializer::with_read(read::SliceRead::new(input), options)
+    }
+}
+
+impl<R> Deserializer<read::IoRead<R>>
+where
+    R: io::Read,
+{
+    /// Creates a Bencode deserializer pulling its input from an `io::Read`.
+    ///
+    /// Byte strings are copied out of the reader, so only types that do not
+    /// borrow from the input can be deserialized.
+    pub fn from_reader(reader: R) -> Self {
+        Deserializer::from_reader_with_options(reader, DeserializerOptions::default())
+    }
+
+    pub fn from_reader_with_options(reader: R, options: DeserializerOptions) -> Self {
+        Deserializer::with_read(read::IoRead::new(reader), options)
     }
 }
 
@@ -27,67 +270,208 @@ pub fn from_bytes<'de, T>(bytes: &'de [u8]) -> Result<T>
 where
     T: Deserialize<'de>,
 {
-    let mut de = Deserializer::new(bytes);
-    let value = T::deserialize(&mut de)?;
+    DeserializerOptions::default().from_bytes(bytes)
+}
+
+/// Deserializes a value from the beginning of `bytes`, returning it along with
+/// the number of bytes it spans. Unlike `from_bytes`, data may follow the
+/// value, such as the raw piece data after a BEP 9 `ut_metadata` message.
+pub fn from_bytes_prefix<'de, T>(bytes: &'de [u8]) -> Result<(T, usize)>
+where
+    T: Deserialize<'de>,
+{
+    DeserializerOptions::default().from_bytes_prefix(bytes)
+}
+
+/// Iterator over the values of a stream of concatenated Bencode documents,
+/// created by `Deserializer::into_iter`.
+///
+/// Iteration ends at the end of the input, or after the first error.
+pub struct StreamDeserializer<'de, R, T> {
+    de: Deserializer<R>,
+    failed: bool,
+    output: PhantomData<T>,
+    lifetime: PhantomData<&'de ()>,
+}
+
+impl<'de, R, T> StreamDeserializer<'de, R, T>
+where
+    R: Read<'de>,
+    T: Deserialize<'de>,
+{
+    pub fn new(read: R) -> Self {
+        Deserializer::with_read(read, DeserializerOptions::default()).into_iter()
+    }
+
+    /// Number of bytes consumed so far, i.e. the end of the last value
+    /// returned.
+    pub fn byte_offset(&self) -> usize {
+        self.de.byte_offset()
+    }
+}
+
+impl<'de, R, T> Iterator for StreamDeserializer<'de, R, T>
+where
+    R: Read<'de>,
+    T: Deserialize<'de>,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.failed {
+            return None;
+        }
 
-    de.end()?;
+        let result = match self.de.read.peek() {
+            Ok(None) => return None,
+            Ok(Some(_)) => T::deserialize(&mut self.de),
+            Err(e) => Err(e),
+        };
+        self.failed = result.is_err();
 
-    Ok(value)
+        Some(result)
+    }
 }
 
-impl<'de> Deserializer<'de> {
+/// Deserializes an instance of type `T` from an I/O stream of Bencode.
+///
+/// The whole stream is expected to hold a single value: anything left after
+/// it is reported as trailing characters.
+pub fn from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: io::Read,
+    T: DeserializeOwned,
+{
+    DeserializerOptions::default().from_reader(reader)
+}
+
+impl<'de, R> Deserializer<R>
+where
+    R: Read<'de>,
+{
+    /// Number of bytes consumed from the input so far. After deserializing a
+    /// value, this is where anything following it starts.
+    #[inline]
+    pub fn byte_offset(&self) -> usize {
+        self.read.index()
+    }
+
+    /// Turns the deserializer into an iterator over successive values of type
+    /// `T` in the input.
+    #[allow(clippy::should_implement_trait)]
+    pub fn into_iter<T>(self) -> StreamDeserializer<'de, R, T>
+    where
+        T: Deserialize<'de>,
+    {
+        StreamDeserializer {
+            de: self,
+            failed: false,
+            output: PhantomData,
+            lifetime: PhantomData,
+        }
+    }
+
+    /// Checks that the whole input has been consumed.
     #[inline]
     pub fn end(&mut self) -> Result<()> {
-        if self.index >= self.input.len() {
-            Ok(())
-        } else {
-            Err(Error::syntax(ErrorKind::TrailingCharacters, self.index))
+        match self.read.peek()? {
+            None => Ok(()),
+            Some(_) => Err(Error::syntax(ErrorKind::TrailingCharacters, self.read.index())),
         }
     }
 
     #[inline]
-    fn peek_byte(&self) -> Result<u8> {
-        if self.index < self.input.len() {
-            Ok(self.input[self.index])
-        } else {
-            Err(Error::eof(self.index))
+    pub(crate) fn peek_byte(&mut self) -> Result<u8> {
+        self.check_input_len(1)?;
+        match self.read.peek()? {
+            Some(b) => Ok(b),
+            None => Err(Error::eof(self.read.index())),
+        }
+    }
+
+    /// Like `peek_byte`, but returns `None` at the end of the input.
+    #[inline]
+    fn peek_byte_or_eof(&mut self) -> Result<Option<u8>> {
+        self.check_input_len(1)?;
+        self.read.peek()
+    }
+
+    #[inline]
+    pub(crate) fn next_byte(&mut self) -> Result<u8> {
+        self.check_input_len(1)?;
+        match self.read.next()? {
+            Some(b) => Ok(b),
+            None => Err(Error::eof(self.read.index())),
         }
     }
 
+    /// Checks that `len` more bytes may be consumed.
     #[inline]
-    fn next_byte(&mut self) -> Result<u8> {
-        if self.index < self.input.len() {
-            let b = self.input[self.index];
-            self.index += 1;
-            Ok(b)
+    fn check_input_len(&self, len: usize) -> Result<()> {
+        let index = self.read.index();
+        if len > self.options.max_input_len.saturating_sub(index) {
+            Err(self.error_with_index(ErrorKind::InputTooLong, index.max(self.options.max_input_len)))
         } else {
-            Err(Error::eof(self.index))
+            Ok(())
         }
     }
 
     #[cold]
     fn error(&self, reason: ErrorKind) -> Error {
-        Error::syntax(reason, self.index - 1)
+        Error::syntax(reason, self.read.index() - 1)
     }
 
     #[cold]
-    fn error_with_index(&self, reason: ErrorKind, index: usize) -> Error {
+    pub(crate) fn error_with_index(&self, reason: ErrorKind, index: usize) -> Error {
         Error::syntax(reason, index)
     }
 
+    /// Enters a nested list, dictionary or enum whose opening byte has just
+    /// been consumed. Must be paired with `leave_nested`.
+    #[inline]
+    pub(crate) fn enter_nested(&mut self) -> Result<()> {
+        if self.remaining_depth == 0 {
+            return Err(self.error(ErrorKind::RecursionLimitExceeded));
+        }
+        self.remaining_depth -= 1;
+        self.top_level = false;
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn leave_nested(&mut self) {
+        self.remaining_depth += 1;
+    }
+
+    /// Counts one more element of a list or dictionary, failing with `kind`
+    /// if it goes beyond `max`.
+    #[inline]
+    pub(crate) fn count_element(&self, len: &mut usize, max: usize, kind: ErrorKind) -> Result<()> {
+        if *len >= max {
+            return Err(self.error_with_index(kind, self.read.index()));
+        }
+        *len += 1;
+        Ok(())
+    }
+
     fn parse_bool(&mut self) -> Result<bool> {
-        let bytes = [self.next_byte()?; 3];
+        let start_index = self.read.index();
+        if self.next_byte()? != b'i' {
+            return Err(self.error(ErrorKind::ExpectedBoolean));
+        }
 
-        match &bytes {
-            b"i1e" => Ok(true),
-            b"i0e" => Ok(false),
-            _ => Err(self.error(ErrorKind::ExpectedBoolean)),
+        // Any magnitude will do for `NonZero`, however large.
+        let (positive, n) = self.parse_magnitude(false, start_index)?;
+        match (positive, n, self.options.bool_policy) {
+            (_, Some(0), _) => Ok(false),
+            (true, Some(1), _) | (_, _, BoolPolicy::NonZero) => Ok(true),
+            _ => Err(self.error_with_index(ErrorKind::ExpectedBoolean, start_index)),
         }
     }
 
     fn parse_number<T>(&mut self) -> Result<T>
     where
-        T: AddAssign<T> + MulAssign<T> + FromPrimitive,
+        T: FromPrimitive,
     {
         if self.next_byte()? != b'i' {
             return Err(self.error(ErrorKind::ExpectedInteger));
@@ -96,22 +480,184 @@ impl<'de> Deserializer<'de> {
         self.parse_integer(false)
     }
 
-    fn parse_bytes(&mut self) -> Result<&'de [u8]> {
+    fn parse_float<F>(&mut self) -> Result<F>
+    where
+        F: FromPrimitive + str::FromStr,
+    {
+        let policy = self.options.float_policy;
+        match policy {
+            FloatPolicy::Decimal if self.peek_byte()? != b'i' => {
+                let start_index = self.read.index();
+                let float = str::from_utf8(&self.parse_bytes()?).ok().and_then(|s| s.parse().ok());
+                float.ok_or_else(|| self.error_with_index(ErrorKind::ExpectedFloat, start_index))
+            },
+            FloatPolicy::Scaled(scale) => {
+                let start_index = self.read.index();
+                let scaled: f64 = self.parse_number()?;
+                scale_factor(scale)
+                    .and_then(|factor| F::from_f64(scaled / factor))
+                    .ok_or_else(|| self.error_with_index(ErrorKind::ExpectedFloat, start_index))
+            },
+            _ => self.parse_number(),
+        }
+    }
+
+    fn parse_bytes(&mut self) -> Result<Reference<'de, '_, [u8]>> {
+        self.parse_bytes_with_end().map(|(bytes, _)| bytes)
+    }
+
+    /// Like `parse_bytes`, but also returns the index right past the string.
+    pub(crate) fn parse_bytes_with_end(&mut self) -> Result<(Reference<'de, '_, [u8]>, usize)> {
+        let start_index = self.read.index();
+        let length: usize = self.parse_integer(true)?;
+
+        if length > self.options.max_bytes_len {
+            return Err(self.error_with_index(ErrorKind::BytesTooLong, start_index));
+        }
+        self.check_input_len(length)?;
+
+        let end = self.read.index().saturating_add(length);
+
+        self.read.parse_bytes(length, &mut self.scratch).map(|b| (b, end))
+    }
+
+    /// Consumes a byte string, checking its length against the limits
+    /// without reading it.
+    fn skip_bytes(&mut self) -> Result<()> {
+        let start_index = self.read.index();
         let length: usize = self.parse_integer(true)?;
 
-        let s = self.input
-            .get(self.index..self.index + length)
-            .ok_or_else(|| Error::eof(self.input.len()))?;
-        self.index += length;
+        if length > self.options.max_bytes_len {
+            return Err(self.error_with_index(ErrorKind::BytesTooLong, start_index));
+        }
+        self.check_input_len(length)?;
+
+        self.read.skip_bytes(length)
+    }
+
+    fn parse_str(&mut self) -> Result<Reference<'de, '_, str>> {
+        let (bytes, end) = self.parse_bytes_with_end()?;
+
+        to_str(bytes, end)
+    }
+
+    /// Consumes a whole value, only checking that it is well formed.
+    fn ignore_value(&mut self) -> Result<()> {
+        match self.peek_byte()? {
+            b'i' => {
+                self.next_byte()?;
+                self.parse_magnitude(false, self.read.index() - 1)?;
+            },
+            b'0'..=b'9' => {
+                self.skip_bytes()?;
+            },
+            b'l' => {
+                self.next_byte()?;
+                self.enter_nested()?;
+                let res = self.ignore_list();
+                self.leave_nested();
+                res?;
+            },
+            b'd' => {
+                self.next_byte()?;
+                self.enter_nested()?;
+                let res = self.ignore_dict();
+                self.leave_nested();
+                res?;
+            },
+            _ => return Err(self.error_with_index(ErrorKind::ExpectedSomeValue, self.read.index())),
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn ignore_list(&mut self) -> Result<()> {
+        let mut len = 0;
+        loop {
+            match self.peek_byte()? {
+                b'e' => break,
+                b'l' | b'd' | b'i' | b'0'..=b'9' => {
+                    self.count_element(&mut len, self.options.max_list_len, ErrorKind::ListTooLong)?;
+                    let start_index = self.read.index();
+                    self.ignore_value()
+                        .map_err(|e| e.nested(PathSegment::Index(len - 1), Some(start_index)))?;
+                },
+                _ => return Err(self.error_with_index(ErrorKind::ExpectedEnd, self.read.index())),
+            }
+        }
+        self.next_byte()?;
+        Ok(())
+    }
+
+    pub(crate) fn ignore_dict(&mut self) -> Result<()> {
+        let mut len = 0;
+        let mut last_key: Option<Vec<u8>> = None;
+        loop {
+            match self.peek_byte()? {
+                b'e' => break,
+                b'0'..=b'9' => {
+                    self.count_element(&mut len, self.options.max_dict_len, ErrorKind::DictTooLong)?;
+                    let start_index = self.read.index();
+                    let strict = self.options.strict;
+                    let key = self.parse_bytes()?;
+
+                    if strict {
+                        if let Some(ref last_key) = last_key {
+                            match (*key).cmp(last_key) {
+                                Ordering::Less => return Err(Error::syntax(ErrorKind::UnsortedKeys, start_index)),
+                                Ordering::Equal => return Err(Error::syntax(ErrorKind::DuplicateKey, start_index)),
+                                Ordering::Greater => {},
+                            }
+                        }
+                    }
+                    let current_key = last_key.get_or_insert_with(Vec::new);
+                    current_key.clear();
+                    current_key.extend_from_slice(&key);
+
+                    let value_index = self.read.index();
+                    if let Err(e) = self.ignore_value() {
+                        let key = last_key.take().unwrap_or_default();
+                        return Err(e.nested(PathSegment::Key(key), Some(value_index)));
+                    }
+                },
+                b'l' | b'd' | b'i' => return Err(self.error_with_index(ErrorKind::KeyMustBeAString, self.read.index())),
+                _ => return Err(self.error_with_index(ErrorKind::ExpectedEnd, self.read.index())),
+            }
+        }
+        self.next_byte()?;
+        Ok(())
+    }
 
-        Ok(s)
+    fn deserialize_raw_value<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.read.begin_raw_buffering();
+        self.ignore_value()?;
+        self.read.end_raw_buffering(visitor)
     }
 
     fn parse_integer<T>(&mut self, parsing_str: bool) -> Result<T>
     where
-        T: AddAssign<T> + MulAssign<T> + FromPrimitive,
+        T: FromPrimitive,
     {
-        let start_index = if parsing_str { self.index } else { self.index - 1 };
+        let start_index = if parsing_str { self.read.index() } else { self.read.index() - 1 };
+        let (positive, n) = self.parse_magnitude(parsing_str, start_index)?;
+
+        let value = match n {
+            Some(n) if positive => T::from_u128(n),
+            Some(n) => 0i128.checked_sub_unsigned(n).and_then(T::from_i128),
+            None => None,
+        };
+
+        value.ok_or_else(|| self.error_with_index(ErrorKind::IntegerOutOfRange, start_index))
+    }
+
+    /// Parses the digits of an integer or string length up to and including
+    /// the end delimiter, returning its sign and magnitude. Magnitudes that
+    /// do not fit in a `u128` are returned as `None`, their decimal digits
+    /// being left in `scratch`.
+    pub(crate) fn parse_magnitude(&mut self, parsing_str: bool, start_index: usize) -> Result<(bool, Option<u128>)> {
         let end = if parsing_str { b':' } else { b'e' };
         let expected = if parsing_str { ErrorKind::ExpectedString }
                                 else { ErrorKind::ExpectedInteger };
@@ -119,7 +665,7 @@ impl<'de> Deserializer<'de> {
                                     else { ErrorKind::ExpectedEnd };
         let mut positive = true;
         let mut first_iter = true;
-        let mut n = 0u64;
+        let mut n = Some(0u128);
 
         loop {
             match self.next_byte()? {
@@ -157,7 +703,7 @@ impl<'de> Deserializer<'de> {
                         // '0e'
                         c if c == end => {
                             self.next_byte()?;
-                            return Ok(FromPrimitive::from_u64(n).unwrap());
+                            return Ok((positive, n));
                         },
                         // '0(0..9)'
                         b'0'..=b'9' => return Err(self.error(ErrorKind::LeadingZero)),
@@ -165,17 +711,19 @@ impl<'de> Deserializer<'de> {
                         _ => {},
                     }
                 },
-                c @ b'1'..=b'9' if first_iter => n = (c - b'0') as u64,
-                c @ b'0'..=b'9' => n = n * 10 + (c - b'0') as u64,
-                c if c == end && !first_iter => {
-                    if positive {
-                        return FromPrimitive::from_u64(n)
-                            .ok_or_else(|| self.error_with_index(ErrorKind::IntegerOutOfRange, start_index));
-                    } else {
-                        return FromPrimitive::from_i64((n as i64).wrapping_neg())
-                            .ok_or_else(|| self.error_with_index(ErrorKind::IntegerOutOfRange, start_index));
-                    }
+                c @ b'1'..=b'9' if first_iter => n = Some((c - b'0') as u128),
+                c @ b'0'..=b'9' => match n {
+                    Some(m) => {
+                        n = m.checked_mul(10).and_then(|m| m.checked_add((c - b'0') as u128));
+                        if n.is_none() {
+                            self.scratch.clear();
+                            self.scratch.extend_from_slice(itoa::Buffer::new().format(m).as_bytes());
+                            self.scratch.push(c);
+                        }
+                    },
+                    None => self.scratch.push(c),
                 },
+                c if c == end && !first_iter => return Ok((positive, n)),
                 _ if first_iter => return Err(self.error_with_index(expected, start_index)),
                 _ => return Err(self.error(expected_end)),
             }
@@ -183,9 +731,55 @@ impl<'de> Deserializer<'de> {
             if first_iter { first_iter = false; }
         }
     }
+
+    /// Parses an integer of unknown width, visiting it as the narrowest of
+    /// `i64`, `u64`, `i128` and `u128` that holds it. With the `bigint`
+    /// feature, wider integers are visited as a newtype struct around their
+    /// decimal digits.
+    fn parse_any_integer<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if self.next_byte()? != b'i' {
+            return Err(self.error(ErrorKind::ExpectedInteger));
+        }
+
+        let start_index = self.read.index() - 1;
+        let (positive, n) = self.parse_magnitude(false, start_index)?;
+
+        match n {
+            Some(n) if positive => match u64::try_from(n) {
+                Ok(n) if n <= i64::MAX as u64 => visitor.visit_i64(n as i64),
+                Ok(n) => visitor.visit_u64(n),
+                Err(_) => visitor.visit_u128(n),
+            },
+            Some(n) if n <= i128::MAX as u128 + 1 => match i64::try_from(0i128.wrapping_sub_unsigned(n)) {
+                Ok(i) => visitor.visit_i64(i),
+                Err(_) => visitor.visit_i128(0i128.wrapping_sub_unsigned(n)),
+            },
+            #[cfg(feature = "bigint")]
+            _ => {
+                let digits = match n {
+                    Some(n) => itoa::Buffer::new().format(n).to_string(),
+                    None => String::from_utf8_lossy(&self.scratch).into_owned(),
+                };
+                let digits = if positive { digits } else { format!("-{}", digits) };
+                visitor.visit_newtype_struct(IntoDeserializer::<Error>::into_deserializer(digits))
+            },
+            #[cfg(not(feature = "bigint"))]
+            _ => Err(self.error_with_index(ErrorKind::IntegerOutOfRange, start_index)),
+        }
+    }
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+fn to_str<'de, 's>(bytes: Reference<'de, 's, [u8]>, end: usize) -> Result<Reference<'de, 's, str>> {
+    match bytes {
+        Reference::Borrowed(b) => str::from_utf8(b).map(Reference::Borrowed),
+        Reference::Copied(c) => str::from_utf8(c).map(Reference::Copied),
+    }.map_err(|_| Error::syntax(ErrorKind::StringNotUtf8, end.saturating_sub(1)))
+}
+
+impl<'de, R: Read<'de>> de::Deserializer<'de> for &mut Deserializer<R> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
@@ -193,11 +787,14 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>
     {
         match self.peek_byte()? {
-            b'i' => self.deserialize_i64(visitor),
-            b'0'..=b'9' => self.deserialize_str(visitor),
+            b'i' => self.parse_any_integer(visitor),
+            b'0'..=b'9' => {
+                let string_policy = self.options.string_policy;
+                visit_any_bytes(self.parse_bytes()?, string_policy, visitor)
+            },
             b'l' => self.deserialize_seq(visitor),
             b'd' => self.deserialize_map(visitor),
-            _ => Err(self.error(ErrorKind::ExpectedSomeValue)),
+            _ => Err(self.error_with_index(ErrorKind::ExpectedSomeValue, self.read.index())),
         }
     }
 
@@ -236,6 +833,13 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_i64(self.parse_number()?)
     }
 
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_i128(self.parse_number()?)
+    }
+
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
@@ -264,30 +868,39 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_u64(self.parse_number()?)
     }
 
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_u128(self.parse_number()?)
+    }
+
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_f32(self.parse_number()?)
+        visitor.visit_f32(self.parse_float()?)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_f64(self.parse_number()?)
+        visitor.visit_f64(self.parse_float()?)
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let ch = self.parse_bytes()?;
-        if ch.len() == 1 {
+        let ch = match *self.parse_bytes()? {
+            [c] => Some(c),
+            _ => None,
+        };
+        match ch {
             // TODO: maybe utf8 str
-            visitor.visit_char(ch[0] as char)
-        } else {
-            Err(self.error(ErrorKind::ExpectedChar))
+            Some(c) => visitor.visit_char(c as char),
+            None => Err(self.error(ErrorKind::ExpectedChar)),
         }
     }
 
@@ -295,9 +908,9 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        match str::from_utf8(self.parse_bytes()?) {
-            Ok(s) => visitor.visit_borrowed_str(s),
-            Err(_) => Err(self.error(ErrorKind::StringNotUtf8)),
+        match self.parse_str()? {
+            Reference::Borrowed(s) => visitor.visit_borrowed_str(s),
+            Reference::Copied(s) => visitor.visit_str(s),
         }
     }
 
@@ -312,24 +925,29 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_borrowed_bytes(self.parse_bytes()?)
+        match self.parse_bytes()? {
+            Reference::Borrowed(b) => visitor.visit_borrowed_bytes(b),
+            Reference::Copied(b) => visitor.visit_bytes(b),
+        }
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_bytes(self.parse_bytes()?)
+        visitor.visit_bytes(&self.parse_bytes()?)
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        if self.peek_byte().is_err() {
-            visitor.visit_unit()
-        } else {
-            visitor.visit_some(self)
+        // Absent dictionary entries are handled by `missing_field`; only an
+        // empty document as a whole stands for `None`. Inside a container,
+        // running out of input is an error like anywhere else.
+        match self.peek_byte_or_eof()? {
+            None if self.top_level => visitor.visit_none(),
+            _ => visitor.visit_some(self),
         }
     }
 
@@ -353,12 +971,20 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
 
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if name == raw::TOKEN {
+            return self.deserialize_raw_value(visitor);
+        }
+        #[cfg(feature = "bigint")]
+        if name == bigint::TOKEN {
+            return self.parse_any_integer(visitor);
+        }
+
         visitor.visit_newtype_struct(self)
     }
 
@@ -367,7 +993,10 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         if self.next_byte()? == b'l' {
-            let value = visitor.visit_seq(SeqAccess::new(self))?;
+            self.enter_nested()?;
+            let value = visitor.visit_seq(SeqAccess::new(self));
+            self.leave_nested();
+            let value = value?;
 
             if self.next_byte()? != b'e' {
                 Err(self.error(ErrorKind::ExpectedEnd))
@@ -403,7 +1032,10 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         if self.next_byte()? == b'd' {
-            let value = visitor.visit_map(MapAccess::new(self))?;
+            self.enter_nested()?;
+            let value = visitor.visit_map(MapAccess::new(self));
+            self.leave_nested();
+            let value = value?;
 
             if self.next_byte()? != b'e' {
                 Err(self.error(ErrorKind::ExpectedEnd))
@@ -437,14 +1069,20 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         match self.peek_byte()? {
-            b'0'..=b'9' => {
-                let s = str::from_utf8(self.parse_bytes()?)
-                    .map_err(|_| self.error(ErrorKind::StringNotUtf8))?;
-                visitor.visit_enum(s.into_deserializer())
+            b'0'..=b'9' => match self.parse_str()? {
+                Reference::Borrowed(s) => visitor.visit_enum(s.into_deserializer()),
+                Reference::Copied(s) => visitor.visit_enum(s.into_deserializer()),
+            },
+            b'i' if self.options.enum_repr == EnumRepr::Index => {
+                let index: u32 = self.parse_number()?;
+                visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(index))
             },
             b'd' => {
                 self.next_byte()?;
-                let value = visitor.visit_enum(EnumAccess::new(self))?;
+                self.enter_nested()?;
+                let value = visitor.visit_enum(EnumAccess::new(self));
+                self.leave_nested();
+                let value = value?;
 
                 if self.next_byte()? != b'e' {
                     Err(self.error(ErrorKind::ExpectedEnd))
@@ -452,7 +1090,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
                     Ok(value)
                 }
             },
-            _ => Err(self.error(ErrorKind::ExpectedEnum)),
+            _ => Err(self.error_with_index(ErrorKind::ExpectedEnum, self.read.index())),
         }
     }
 
@@ -463,25 +1101,29 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self.deserialize_str(visitor)
     }
 
+    /// Skips the value, only checking that it is well formed: byte strings are
+    /// jumped over by their length and never reach the visitor.
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_any(visitor)
+        self.ignore_value()?;
+        visitor.visit_unit()
     }
 }
 
-struct SeqAccess<'a, 'de: 'a> {
-    de: &'a mut Deserializer<'de>,
+struct SeqAccess<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+    len: usize,
 }
 
-impl<'a, 'de: 'a> SeqAccess<'a, 'de> {
-    fn new(de: &'a mut Deserializer<'de>) -> Self {
-        SeqAccess { de }
+impl<'a, R: 'a> SeqAccess<'a, R> {
+    fn new(de: &'a mut Deserializer<R>) -> Self {
+        SeqAccess { de, len: 0 }
     }
 }
 
-impl<'de, 'a> de::SeqAccess<'de> for SeqAccess<'a, 'de> {
+impl<'de, R: Read<'de>> de::SeqAccess<'de> for SeqAccess<'_, R> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -491,13 +1133,17 @@ impl<'de, 'a> de::SeqAccess<'de> for SeqAccess<'a, 'de> {
         match self.de.peek_byte()? {
             b'e' => Ok(None),
             b'l' | b'd' | b'i' | b'0'..=b'9' => {
-                seed.deserialize(&mut *self.de).map(Some)
+                self.de.count_element(&mut self.len, self.de.options.max_list_len, ErrorKind::ListTooLong)?;
+                let start_index = self.de.read.index();
+                seed.deserialize(&mut *self.de)
+                    .map(Some)
+                    .map_err(|e| e.nested(PathSegment::Index(self.len - 1), Some(start_index)))
             },
             _ => {
                 Err(
                     self.de.error_with_index(
                         ErrorKind::ExpectedEnd,
-                        self.de.index
+                        self.de.read.index()
                     )
                 )
             },
@@ -505,17 +1151,27 @@ impl<'de, 'a> de::SeqAccess<'de> for SeqAccess<'a, 'de> {
     }
 }
 
-struct MapAccess<'a, 'de: 'a> {
-    de: &'a mut Deserializer<'de>,
+struct MapAccess<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
+    len: usize,
+    /// Current key, compared against the next one in strict mode and
+    /// reported in the path of errors.
+    key: Option<Vec<u8>>,
 }
 
-impl<'a, 'de: 'a> MapAccess<'a, 'de> {
-    fn new(de: &'a mut Deserializer<'de>) -> Self {
-        MapAccess { de }
+impl<'a, R: 'a> MapAccess<'a, R> {
+    fn new(de: &'a mut Deserializer<R>) -> Self {
+        MapAccess { de, len: 0, key: None }
+    }
+
+    #[cold]
+    fn nested(&self, err: Error, index: usize) -> Error {
+        let key = self.key.clone().unwrap_or_default();
+        err.nested(PathSegment::Key(key), Some(index))
     }
 }
 
-impl<'de, 'a> de::MapAccess<'de> for MapAccess<'a, 'de> {
+impl<'de, R: Read<'de>> de::MapAccess<'de> for MapAccess<'_, R> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
@@ -524,13 +1180,37 @@ impl<'de, 'a> de::MapAccess<'de> for MapAccess<'a, 'de> {
     {
         match self.de.peek_byte()? {
             b'e' => Ok(None),
-            b'0'..=b'9' => seed.deserialize(&mut *self.de).map(Some),
-            b'l' | b'd' | b'i' => Err(self.de.error(ErrorKind::KeyMustBeAString)),
+            b'0'..=b'9' => {
+                self.de.count_element(&mut self.len, self.de.options.max_dict_len, ErrorKind::DictTooLong)?;
+                let start_index = self.de.read.index();
+                let strict = self.de.options.strict;
+                let string_policy = self.de.options.string_policy;
+                let (key, end) = self.de.parse_bytes_with_end()?;
+
+                if strict {
+                    if let Some(ref last_key) = self.key {
+                        match (*key).cmp(last_key) {
+                            Ordering::Less => return Err(Error::syntax(ErrorKind::UnsortedKeys, start_index)),
+                            Ordering::Equal => return Err(Error::syntax(ErrorKind::DuplicateKey, start_index)),
+                            Ordering::Greater => {},
+                        }
+                    }
+                }
+                let current_key = self.key.get_or_insert_with(Vec::new);
+                current_key.clear();
+                current_key.extend_from_slice(&key);
+
+                match seed.deserialize(MapKey { key, end, string_policy }) {
+                    Ok(key) => Ok(Some(key)),
+                    Err(e) => Err(self.nested(e, start_index)),
+                }
+            },
+            b'l' | b'd' | b'i' => Err(self.de.error_with_index(ErrorKind::KeyMustBeAString, self.de.read.index())),
             _ => {
                 Err(
                     self.de.error_with_index(
                         ErrorKind::ExpectedEnd,
-                        self.de.index
+                        self.de.read.index()
                     )
                 )
             },
@@ -541,21 +1221,141 @@ impl<'de, 'a> de::MapAccess<'de> for MapAccess<'a, 'de> {
     where
         V: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self.de)
+        let start_index = self.de.read.index();
+        match seed.deserialize(&mut *self.de) {
+            Ok(value) => Ok(value),
+            Err(e) => Err(self.nested(e, start_index)),
+        }
+    }
+}
+
+/// Visits a byte string on behalf of `deserialize_any`.
+fn visit_any_bytes<'de, V>(bytes: Reference<'de, '_, [u8]>, policy: StringPolicy, visitor: V) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    match (bytes, policy) {
+        (Reference::Borrowed(b), StringPolicy::Utf8OrBytes) => match str::from_utf8(b) {
+            Ok(s) => visitor.visit_borrowed_str(s),
+            Err(_) => visitor.visit_borrowed_bytes(b),
+        },
+        (Reference::Copied(b), StringPolicy::Utf8OrBytes) => match str::from_utf8(b) {
+            Ok(s) => visitor.visit_str(s),
+            Err(_) => visitor.visit_bytes(b),
+        },
+        (Reference::Borrowed(b), StringPolicy::Bytes) => visitor.visit_borrowed_bytes(b),
+        (Reference::Copied(b), StringPolicy::Bytes) => visitor.visit_bytes(b),
+    }
+}
+
+/// Deserializer for a dictionary key that has already been read.
+struct MapKey<'de, 's> {
+    key: Reference<'de, 's, [u8]>,
+    end: usize,
+    string_policy: StringPolicy,
+}
+
+impl<'de> de::Deserializer<'de> for MapKey<'de, '_> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visit_any_bytes(self.key, self.string_policy, visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match to_str(self.key, self.end)? {
+            Reference::Borrowed(s) => visitor.visit_borrowed_str(s),
+            Reference::Copied(s) => visitor.visit_str(s),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.key {
+            Reference::Borrowed(b) => visitor.visit_borrowed_bytes(b),
+            Reference::Copied(b) => visitor.visit_bytes(b),
+        }
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_bytes(&self.key)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match to_str(self.key, self.end)? {
+            Reference::Borrowed(s) => visitor.visit_enum(s.into_deserializer()),
+            Reference::Copied(s) => visitor.visit_enum(s.into_deserializer()),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char
+        unit unit_struct seq tuple tuple_struct map struct ignored_any
     }
 }
 
-struct EnumAccess<'a, 'de: 'a> {
-    de: &'a mut Deserializer<'de>,
+struct EnumAccess<'a, R: 'a> {
+    de: &'a mut Deserializer<R>,
 }
 
-impl<'a, 'de: 'a> EnumAccess<'a, 'de> {
-    fn new(de: &'a mut Deserializer<'de>) -> Self {
+impl<'a, R: 'a> EnumAccess<'a, R> {
+    fn new(de: &'a mut Deserializer<R>) -> Self {
         EnumAccess { de }
     }
 }
 
-impl<'de, 'a> de::EnumAccess<'de> for EnumAccess<'a, 'de> {
+impl<'de, R: Read<'de>> de::EnumAccess<'de> for EnumAccess<'_, R> {
     type Error = Error;
     type Variant = Self;
 
@@ -563,13 +1363,26 @@ impl<'de, 'a> de::EnumAccess<'de> for EnumAccess<'a, 'de> {
     where
         V: DeserializeSeed<'de>,
     {
+        if self.de.options.enum_repr == EnumRepr::Index {
+            let start_index = self.de.read.index();
+            let value = match str::from_utf8(&self.de.parse_bytes()?) {
+                Ok(s) => match s.parse::<u32>() {
+                    Ok(index) => seed.deserialize(IntoDeserializer::<Error>::into_deserializer(index))?,
+                    Err(_) => seed.deserialize(IntoDeserializer::<Error>::into_deserializer(s))?,
+                },
+                Err(_) => return Err(Error::syntax(ErrorKind::ExpectedEnum, start_index)),
+            };
+
+            return Ok((value, self));
+        }
+
         let value = seed.deserialize(&mut *self.de)?;
 
         Ok((value, self))
     }
 }
 
-impl<'de, 'a> de::VariantAccess<'de> for EnumAccess<'a, 'de> {
+impl<'de, R: Read<'de>> de::VariantAccess<'de> for EnumAccess<'_, R> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
@@ -605,15 +1418,19 @@ impl<'de, 'a> de::VariantAccess<'de> for EnumAccess<'a, 'de> {
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
-    use serde::Deserialize;
+    use serde::{Deserialize, Serialize};
 
-    use super::from_bytes;
+    use crate::{FloatPolicy, PathSegment, SerializerOptions};
+    use super::{
+        from_bytes, from_bytes_prefix, from_reader, BoolPolicy, Deserializer, DeserializerOptions,
+        StreamDeserializer, StringPolicy,
+    };
 
     #[test]
     fn test_err_trailing_chars() {
         let i = from_bytes::<'_, i32>(b"i42eabc");
 
-        assert_eq!(i.is_err(), true);
+        assert!(i.is_err());
         assert_eq!(i.unwrap_err().to_string(), "trailing characters at index 4");
     }
 
@@ -621,7 +1438,7 @@ mod tests {
     fn test_err_eof() {
         let i = from_bytes::<'_, i32>(b"");
 
-        assert_eq!(i.is_err(), true);
+        assert!(i.is_err());
         assert_eq!(i.unwrap_err().to_string(), "EOF while parsing at index 0");
     }
 
@@ -638,6 +1455,62 @@ mod tests {
         assert_eq!(l, -10200);
     }
 
+    #[test]
+    fn test_int_range() {
+        assert_eq!(from_bytes::<i64>(b"i-9223372036854775808e").unwrap(), i64::MIN);
+        assert_eq!(from_bytes::<i64>(b"i9223372036854775807e").unwrap(), i64::MAX);
+        assert_eq!(from_bytes::<u64>(b"i18446744073709551615e").unwrap(), u64::MAX);
+        assert_eq!(from_bytes::<i8>(b"i-128e").unwrap(), i8::MIN);
+        assert_eq!(from_bytes::<i128>(b"i-170141183460469231731687303715884105728e").unwrap(), i128::MIN);
+        assert_eq!(from_bytes::<u128>(b"i340282366920938463463374607431768211455e").unwrap(), u128::MAX);
+        assert_eq!(from_bytes::<crate::Value>(b"i-9223372036854775808e").unwrap(), crate::Value::Integer(i64::MIN));
+
+        for n in [i128::MIN, i64::MIN as i128 - 1, -1, u64::MAX as i128 + 1, i128::MAX] {
+            assert_eq!(from_bytes::<i128>(&crate::to_bytes(&n).unwrap()).unwrap(), n);
+        }
+    }
+
+    #[test]
+    fn test_int_range_err() {
+        let a = from_bytes::<u64>(b"i18446744073709551616e");
+        let b = from_bytes::<i64>(b"i-9223372036854775809e");
+        let c = from_bytes::<i64>(b"i9223372036854775808e");
+        let d = from_bytes::<i8>(b"i-129e");
+        let e = from_bytes::<u128>(b"i340282366920938463463374607431768211456e");
+        let f = from_bytes::<i128>(b"i-170141183460469231731687303715884105729e");
+        let g = from_bytes::<&str>(b"99999999999999999999999999999999999999999:");
+        let h = from_bytes::<Vec<u8>>(b"li1ei-99999999999999999999999999999999999999999ee");
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "integer out of range at index 0");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "integer out of range at index 0");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "integer out of range at index 0");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "integer out of range at index 0");
+        assert!(e.is_err());
+        assert_eq!(e.unwrap_err().to_string(), "integer out of range at index 0");
+        assert!(f.is_err());
+        assert_eq!(f.unwrap_err().to_string(), "integer out of range at index 0");
+        assert!(g.is_err());
+        assert_eq!(g.unwrap_err().to_string(), "integer out of range at index 0");
+        assert!(h.is_err());
+        assert_eq!(h.unwrap_err().to_string(), "integer out of range at index 4 in [1]");
+    }
+
+    #[test]
+    fn test_any_int() {
+        use serde::de::IgnoredAny;
+
+        let v: crate::Value = from_bytes(b"li-1ei9223372036854775807ee").unwrap();
+        let big = from_bytes::<crate::Value>(b"i9223372036854775808e");
+
+        assert_eq!(v, crate::Value::List(vec![crate::Value::Integer(-1), crate::Value::Integer(i64::MAX)]));
+        assert_eq!(big.is_err(), cfg!(not(feature = "bigint")));
+        assert!(from_bytes::<IgnoredAny>(b"li340282366920938463463374607431768211455ei-170141183460469231731687303715884105728ee").is_ok());
+    }
+
     #[test]
     fn test_int_err() {
         let i = from_bytes::<'_, i8>(b"i42000e");
@@ -651,25 +1524,25 @@ mod tests {
         let q = from_bytes::<'_, i64>(b"i-0azertye");
         let r = from_bytes::<'_, i64>(b"i-azertye");
 
-        assert_eq!(i.is_err(), true);
+        assert!(i.is_err());
         assert_eq!(i.unwrap_err().to_string(), "integer out of range at index 0");
-        assert_eq!(j.is_err(), true);
+        assert!(j.is_err());
         assert_eq!(j.unwrap_err().to_string(), "integer out of range at index 0");
-        assert_eq!(k.is_err(), true);
+        assert!(k.is_err());
         assert_eq!(k.unwrap_err().to_string(), "`i-0e` is invalid at index 0");
-        assert_eq!(l.is_err(), true);
+        assert!(l.is_err());
         assert_eq!(l.unwrap_err().to_string(), "`i-0e` is invalid at index 0");
-        assert_eq!(m.is_err(), true);
+        assert!(m.is_err());
         assert_eq!(m.unwrap_err().to_string(), "leading zeros are invalid at index 1");
-        assert_eq!(n.is_err(), true);
+        assert!(n.is_err());
         assert_eq!(n.unwrap_err().to_string(), "leading zeros are invalid at index 2");
-        assert_eq!(o.is_err(), true);
+        assert!(o.is_err());
         assert_eq!(o.unwrap_err().to_string(), "expected integer at index 0");
-        assert_eq!(p.is_err(), true);
+        assert!(p.is_err());
         assert_eq!(p.unwrap_err().to_string(), "expected `e` at index 3");
-        assert_eq!(q.is_err(), true);
+        assert!(q.is_err());
         assert_eq!(q.unwrap_err().to_string(), "expected integer at index 0");
-        assert_eq!(r.is_err(), true);
+        assert!(r.is_err());
         assert_eq!(r.unwrap_err().to_string(), "expected integer at index 0");
     }
 
@@ -692,19 +1565,19 @@ mod tests {
         let f = from_bytes::<'_, &str>(b"-6:blabla");
         let g = from_bytes::<'_, &str>(b"blabla");
 
-        assert_eq!(a.is_err(), true);
+        assert!(a.is_err());
         assert_eq!(a.unwrap_err().to_string(), "trailing characters at index 7");
-        assert_eq!(b.is_err(), true);
+        assert!(b.is_err());
         assert_eq!(b.unwrap_err().to_string(), "EOF while parsing at index 5");
-        assert_eq!(c.is_err(), true);
+        assert!(c.is_err());
         assert_eq!(c.unwrap_err().to_string(), "expected `:` at index 1");
-        assert_eq!(d.is_err(), true);
+        assert!(d.is_err());
         assert_eq!(d.unwrap_err().to_string(), "expected `:` at index 2");
-        assert_eq!(e.is_err(), true);
+        assert!(e.is_err());
         assert_eq!(e.unwrap_err().to_string(), "leading zeros are invalid at index 0");
-        assert_eq!(f.is_err(), true);
+        assert!(f.is_err());
         assert_eq!(f.unwrap_err().to_string(), "expected string at index 0");
-        assert_eq!(g.is_err(), true);
+        assert!(g.is_err());
         assert_eq!(g.unwrap_err().to_string(), "expected string at index 0");
     }
 
@@ -717,6 +1590,76 @@ mod tests {
         assert_eq!(o2, None);
     }
 
+    #[test]
+    fn test_float_policy() {
+        let round_trip = |policy, values: &[f64]| {
+            let input = SerializerOptions::new().float_policy(policy).to_bytes(&values).unwrap();
+            DeserializerOptions::new().float_policy(policy).from_bytes::<Vec<f64>>(&input).unwrap()
+        };
+
+        assert_eq!(round_trip(FloatPolicy::Decimal, &[0.1, -2.5, 1e300, 1.0 / 3.0]), [0.1, -2.5, 1e300, 1.0 / 3.0]);
+        assert_eq!(round_trip(FloatPolicy::Scaled(2), &[0.25, -2.5, 1.23]), [0.25, -2.5, 1.23]);
+
+        let scaled = DeserializerOptions::new().float_policy(FloatPolicy::Scaled(2));
+        let decimal = DeserializerOptions::new().float_policy(FloatPolicy::Decimal);
+
+        assert_eq!(scaled.from_bytes::<(f32, f64)>(b"li-250ei33ee").unwrap(), (-2.5, 0.33));
+        assert_eq!(decimal.from_bytes::<(f32, f64)>(b"l3:0.1i7ee").unwrap(), (0.1, 7.0));
+        assert_eq!(from_bytes::<f64>(b"i-7e").unwrap(), -7.0);
+    }
+
+    #[test]
+    fn test_float_policy_err() {
+        let decimal = DeserializerOptions::new().float_policy(FloatPolicy::Decimal);
+
+        let a = decimal.from_bytes::<Vec<f64>>(b"l3:abce");
+        let b = decimal.from_bytes::<f32>(b"le");
+        let c = from_bytes::<f64>(b"3:0.1");
+        let d = DeserializerOptions::new().float_policy(FloatPolicy::Scaled(u32::MAX)).from_bytes::<Vec<f64>>(b"li5ee");
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "expected float at index 1 in [0]");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "expected string at index 0");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "expected integer at index 0");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "expected float at index 1 in [0]");
+    }
+
+    #[test]
+    fn test_option_entries() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct Test { a: Option<i64>, b: Option<Vec<Option<u8>>> }
+
+        let t = Test { a: None, b: Some(vec![Some(1)]) };
+
+        assert_eq!(from_bytes::<Test>(b"de").unwrap(), Test { a: None, b: None });
+        assert_eq!(from_bytes::<Test>(b"d1:ai0ee").unwrap(), Test { a: Some(0), b: None });
+        assert_eq!(from_bytes::<Test>(&crate::to_bytes(&t).unwrap()).unwrap(), t);
+    }
+
+    #[test]
+    fn test_option_entries_err() {
+        #[derive(Debug, Deserialize)]
+        struct Test { _a: Option<i64> }
+
+        let a = from_bytes::<Test>(b"d2:_a");
+        let b = from_bytes::<Vec<Option<i64>>>(b"li1e");
+        let c = from_reader::<_, Test>(&b"d2:_a"[..]);
+        // Only whole documents may be empty.
+        let d = Option::<i64>::deserialize(&mut Deserializer::new(b""));
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "EOF while parsing at index 5 in _a");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "EOF while parsing at index 4");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "EOF while parsing at index 5 in _a");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "EOF while parsing at index 0");
+    }
+
     #[test]
     fn test_unit() {
         let u: () = from_bytes(b"").unwrap();
@@ -728,7 +1671,7 @@ mod tests {
     fn test_unit_err() {
         let u = from_bytes::<'_, ()>(b"aa");
 
-        assert_eq!(u.is_err(), true);
+        assert!(u.is_err());
         assert_eq!(u.unwrap_err().to_string(), "trailing characters at index 0");
     }
 
@@ -749,7 +1692,7 @@ mod tests {
 
         let us = from_bytes::<'_, Test>(b"aa");
 
-        assert_eq!(us.is_err(), true);
+        assert!(us.is_err());
         assert_eq!(us.unwrap_err().to_string(), "trailing characters at index 0");
     }
 
@@ -771,9 +1714,9 @@ mod tests {
         let uv = from_bytes::<'_, Test>(b"1:A");
         let uv2 = from_bytes::<'_, Test>(b"2:B");
 
-        assert_eq!(uv.is_err(), true);
+        assert!(uv.is_err());
         assert_eq!(uv.unwrap_err().to_string(), "unknown variant `A`, expected `B`");
-        assert_eq!(uv2.is_err(), true);
+        assert!(uv2.is_err());
         assert_eq!(uv2.unwrap_err().to_string(), "EOF while parsing at index 3");
     }
 
@@ -816,16 +1759,16 @@ mod tests {
         let v4 = from_bytes::<'_, (u16, u16)>(b"le");
         let v5 = from_bytes::<'_, Vec<i32>>(b"li22e4:teste");
 
-        assert_eq!(v.is_err(), true);
+        assert!(v.is_err());
         assert_eq!(v.unwrap_err().to_string(), "expected `e` at index 7");
-        assert_eq!(v2.is_err(), true);
+        assert!(v2.is_err());
         assert_eq!(v2.unwrap_err().to_string(), "expected `e` at index 11");
-        assert_eq!(v3.is_err(), true);
+        assert!(v3.is_err());
         assert_eq!(v3.unwrap_err().to_string(), "expected list at index 0");
-        assert_eq!(v4.is_err(), true);
+        assert!(v4.is_err());
         assert_eq!(v4.unwrap_err().to_string(), "invalid length 0, expected a tuple of size 2");
-        assert_eq!(v5.is_err(), true);
-        assert_eq!(v5.unwrap_err().to_string(), "expected integer at index 5");
+        assert!(v5.is_err());
+        assert_eq!(v5.unwrap_err().to_string(), "expected integer at index 5 in [1]");
     }
 
     #[test]
@@ -877,4 +1820,447 @@ mod tests {
 
         assert_eq!(sv, Test::A { a: 12345, b: vec!["hello".to_string(), "world".to_string()]});
     }
+
+    #[test]
+    fn test_reader() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Test {
+            name: String,
+            size: u64,
+            #[serde(with = "crate::bytes")]
+            id: [u8; 4],
+            files: Vec<String>,
+        }
+
+        let input: &[u8] = b"d4:name4:test4:sizei1024e2:id4:\x00\x01\x02\x035:filesl1:a1:bee";
+        let t: Test = from_reader(input).unwrap();
+
+        assert_eq!(t, Test {
+            name: "test".to_string(),
+            size: 1024,
+            id: [0, 1, 2, 3],
+            files: vec!["a".to_string(), "b".to_string()],
+        });
+        assert_eq!(from_reader::<_, crate::Value>(input).unwrap(), from_bytes::<crate::Value>(input).unwrap());
+    }
+
+    #[test]
+    fn test_reader_err() {
+        let inputs: [&[u8]; 6] = [b"i42eabc", b"", b"5:bla", b"i0022e", b"li1ei2ea", b"d1:ai1e"];
+
+        for input in inputs {
+            let from_slice = from_bytes::<'_, crate::Value>(input).unwrap_err();
+            let from_io = from_reader::<_, crate::Value>(input).unwrap_err();
+
+            assert_eq!(from_io.to_string(), from_slice.to_string());
+            assert_eq!(from_io.index(), from_slice.index());
+        }
+    }
+
+    #[test]
+    fn test_reader_io_err() {
+        struct Failing;
+
+        impl std::io::Read for Failing {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("connection reset"))
+            }
+        }
+
+        let e = from_reader::<_, i64>(Failing).unwrap_err();
+
+        assert_eq!(e.to_string(), "connection reset");
+        assert_eq!(e.index(), None);
+    }
+
+    #[test]
+    fn test_strict() {
+        let mut de = Deserializer::new(b"d1:ai1e1:bi2e2:bbi3e1:cd0:i0eee");
+        de.set_strict(true);
+        let v = crate::Value::deserialize(&mut de).unwrap();
+
+        assert_eq!(v.get("bb"), Some(&crate::Value::Integer(3)));
+    }
+
+    #[test]
+    fn test_strict_err() {
+        fn strict<'de, T: Deserialize<'de>>(input: &'de [u8]) -> crate::Result<T> {
+            let mut de = Deserializer::new(input);
+            de.set_strict(true);
+            T::deserialize(&mut de)
+        }
+
+        let a = strict::<BTreeMap<&str, i32>>(b"d1:bi1e1:ai2ee");
+        let b = strict::<BTreeMap<&str, i32>>(b"d1:ai1e1:ai2ee");
+        let c = strict::<crate::Value>(b"ld1:ai1e1:bd2:bbi0e1:ai0eeee");
+        let d = strict::<crate::Value>(b"d2:aai1e1:bi2e1:ai3ee");
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "dictionary keys are not sorted at index 7");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "duplicate dictionary key at index 7");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "dictionary keys are not sorted at index 19 in [0].b");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "dictionary keys are not sorted at index 14");
+
+        let lenient: BTreeMap<&str, i32> = from_bytes(b"d1:bi1e1:ai2ee").unwrap();
+        assert_eq!(lenient.len(), 2);
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        #[derive(Debug, Deserialize)]
+        enum Nested { A(Box<Nested>), B }
+
+        let deep_list = [b'l'; 100_000];
+        let deep_enum = b"d1:A".repeat(100_000);
+
+        let a = from_bytes::<'_, crate::Value>(&deep_list);
+        let b = from_bytes::<'_, serde::de::IgnoredAny>(&deep_list);
+        let c = from_bytes::<'_, crate::RawBencode>(&deep_list);
+        let d = from_bytes::<'_, Nested>(&deep_enum);
+        let e = from_reader::<_, crate::Value>(&deep_list[..]);
+
+        let path = "[0]".repeat(128);
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), format!("recursion limit exceeded at index 128 in {}", path));
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), format!("recursion limit exceeded at index 128 in {}", path));
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), format!("recursion limit exceeded at index 128 in {}", path));
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "recursion limit exceeded at index 512");
+        assert!(matches!(from_bytes(b"d1:Ad1:A1:Bee"), Ok(Nested::A(n)) if matches!(*n, Nested::A(_))));
+        assert!(e.is_err());
+        assert_eq!(e.unwrap_err().to_string(), format!("recursion limit exceeded at index 128 in {}", path));
+    }
+
+    #[test]
+    fn test_recursion_limit_setting() {
+        let input = [&[b'l'; 4][..], &[b'e'; 4][..]].concat();
+
+        let mut de = Deserializer::new(&input);
+        de.set_recursion_limit(4);
+        assert!(crate::Value::deserialize(&mut de).is_ok());
+
+        let mut de = Deserializer::new(&input);
+        de.set_recursion_limit(3);
+        let e = crate::Value::deserialize(&mut de);
+        assert!(e.is_err());
+        assert_eq!(e.unwrap_err().to_string(), "recursion limit exceeded at index 3 in [0][0][0]");
+    }
+
+    #[test]
+    fn test_limits() {
+        let options = DeserializerOptions::new()
+            .max_bytes_len(4)
+            .max_list_len(2)
+            .max_dict_len(1)
+            .max_input_len(16);
+
+        let v: crate::Value = options.from_bytes(b"d1:ali1e4:abcdee").unwrap();
+        assert_eq!(v.get("a").and_then(crate::Value::as_list).map(Vec::len), Some(2));
+    }
+
+    #[test]
+    fn test_limits_err() {
+        let options = DeserializerOptions::new()
+            .max_bytes_len(4)
+            .max_list_len(2)
+            .max_dict_len(1)
+            .max_input_len(16);
+
+        let a = options.from_bytes::<crate::Value>(b"l999999999:e");
+        let b = options.from_bytes::<crate::Value>(b"li1ei2ei3ee");
+        let c = options.from_bytes::<crate::Value>(b"d1:ai1e1:bi2ee");
+        let d = options.from_bytes::<crate::Value>(b"li1ei2eli3eee");
+        let e = options.from_bytes::<serde::de::IgnoredAny>(b"ld1:ai1e1:bi2eee");
+        let f = options.from_bytes::<crate::RawBencode>(b"li1ei2ei3ee");
+        let g = options.from_bytes::<crate::Value>(b"ll4:abcdel4:abcdee");
+        let h = options.from_reader::<_, Vec<u64>>(&b"li1ei2ei3ee"[..]);
+        let i = options.from_reader::<_, crate::Value>(&b"ll4:abcdel4:abcdee"[..]);
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "byte string exceeds the size limit at index 1 in [0]");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "list exceeds the size limit at index 7");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "dictionary exceeds the size limit at index 7");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "list exceeds the size limit at index 7");
+        assert!(e.is_err());
+        assert_eq!(e.unwrap_err().to_string(), "dictionary exceeds the size limit at index 8 in [0]");
+        assert!(f.is_err());
+        assert_eq!(f.unwrap_err().to_string(), "list exceeds the size limit at index 7");
+        assert!(g.is_err());
+        assert_eq!(g.unwrap_err().to_string(), "input exceeds the size limit at index 16 in [1]");
+        assert!(h.is_err());
+        assert_eq!(h.unwrap_err().to_string(), "list exceeds the size limit at index 7");
+        assert!(i.is_err());
+        assert_eq!(i.unwrap_err().to_string(), "input exceeds the size limit at index 16 in [1]");
+    }
+
+    #[test]
+    fn test_bool() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct Flags {
+            private: bool,
+            seed: bool,
+        }
+
+        let flags = Flags { private: true, seed: false };
+        let bytes = crate::to_bytes(&flags).unwrap();
+        let lenient = DeserializerOptions::new().bool_policy(BoolPolicy::NonZero);
+
+        assert_eq!(bytes, b"d7:privatei1e4:seedi0ee");
+        assert_eq!(from_bytes::<Flags>(&bytes).unwrap(), flags);
+        assert_eq!(from_reader::<_, Flags>(&bytes[..]).unwrap(), flags);
+        assert_eq!(from_bytes::<Vec<bool>>(b"li1ei0ee").unwrap(), vec![true, false]);
+        assert_eq!(lenient.from_bytes::<Vec<bool>>(b"li0ei1ei2ei-7ee").unwrap(), vec![false, true, true, true]);
+        assert_eq!(lenient.from_bytes::<(bool, bool)>(b"li99999999999999999999999999999999999999999ei-99999999999999999999ee").unwrap(), (true, true));
+    }
+
+    #[test]
+    fn test_bool_err() {
+        let a = from_bytes::<bool>(b"i2e");
+        let b = from_bytes::<Vec<bool>>(b"li1ei-1ee");
+        let c = from_bytes::<bool>(b"1:1");
+        let d = from_bytes::<bool>(b"i1");
+        let e = from_bytes::<bool>(b"i01e");
+        let f = from_bytes::<bool>(b"i99999999999999999999e");
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "expected boolean at index 0");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "expected boolean at index 4 in [1]");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "expected boolean at index 0");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "EOF while parsing at index 2");
+        assert!(e.is_err());
+        assert_eq!(e.unwrap_err().to_string(), "leading zeros are invalid at index 1");
+        assert!(f.is_err());
+        assert_eq!(f.unwrap_err().to_string(), "expected boolean at index 0");
+    }
+
+    #[test]
+    fn test_any_bytes() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[serde(untagged)]
+        enum Peers {
+            Compact(#[serde(with = "crate::bytes")] Vec<u8>),
+            List(Vec<BTreeMap<String, crate::Value>>),
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Info {
+            name: String,
+            #[serde(flatten)]
+            rest: BTreeMap<String, crate::Value>,
+        }
+
+        let input = b"d4:name1:x6:pieces4:\x00\xff\x10\x80e";
+        let info: Info = from_bytes(input).unwrap();
+        let peers: Peers = from_bytes(b"6:\x7f\x00\x00\x01\x1a\xe1").unwrap();
+        let bytes = DeserializerOptions::new().string_policy(StringPolicy::Bytes);
+
+        assert_eq!(info.rest.get("pieces"), Some(&crate::Value::Bytes(vec![0x00, 0xff, 0x10, 0x80])));
+        assert_eq!(from_reader::<_, Info>(&input[..]).unwrap(), info);
+        assert_eq!(peers, Peers::Compact(vec![0x7f, 0x00, 0x00, 0x01, 0x1a, 0xe1]));
+        assert_eq!(from_bytes::<crate::Value>(b"l1:\xffe").unwrap(), crate::Value::List(vec![crate::Value::Bytes(vec![0xff])]));
+        assert!(from_bytes::<serde::de::IgnoredAny>(b"d1:\xff1:\xfee").is_ok());
+        assert!(from_bytes::<String>(b"1:\xff").is_err());
+        assert_eq!(bytes.from_bytes::<Peers>(b"3:abc").unwrap(), Peers::Compact(b"abc".to_vec()));
+    }
+
+    #[test]
+    fn test_any_err() {
+        let a = from_bytes::<crate::Value>(b"x");
+        let b = from_bytes::<crate::Value>(b"d1:ax");
+        let c = from_bytes::<crate::Value>(b"di1ei2ee");
+        let d = from_reader::<_, crate::Value>(&b"d1:ax"[..]);
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "expected value at index 0");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "expected value at index 4 in a");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "key must be a string at index 1");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "expected value at index 4 in a");
+    }
+
+    #[test]
+    fn test_error_path() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct File {
+            length: u64,
+            path: Vec<String>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Info {
+            files: Vec<File>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Torrent {
+            info: Info,
+        }
+
+        let a = from_bytes::<Torrent>(b"d4:infod5:filesld6:lengthi1e4:pathl1:aeed6:lengthi-2e4:pathl1:beeeee");
+        let b = from_bytes::<Torrent>(b"d4:infod5:filesld6:lengthi1e4:pathl1:aeed4:pathl1:beeeee");
+        let c = from_reader::<_, Torrent>(&b"d4:infod5:filesld6:lengthi1e4:pathli7eeeeee"[..]);
+        let d = from_bytes::<BTreeMap<String, u8>>(b"d1:ai1e2:\xff\xfei2ee");
+        let e = from_bytes::<Torrent>(b"d4:infoi0ee");
+
+        assert!(a.is_err());
+        let a = a.unwrap_err();
+        assert_eq!(a.to_string(), "integer out of range at index 49 in info.files[1].length");
+        assert_eq!(a.path().segments(), [
+            PathSegment::Key(b"info".to_vec()),
+            PathSegment::Key(b"files".to_vec()),
+            PathSegment::Index(1),
+            PathSegment::Key(b"length".to_vec()),
+        ]);
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "missing field `length` at index 40 in info.files[1]");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "expected string at index 35 in info.files[0].path[0]");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "strings must be a utf-8 at index 10 in [\"\\xff\\xfe\"]");
+        assert!(e.is_err());
+        assert!(e.unwrap_err().path().segments() == [PathSegment::Key(b"info".to_vec())]);
+        assert!(from_bytes::<u8>(b"i-1e").unwrap_err().path().is_empty());
+    }
+
+    #[test]
+    fn test_prefix() {
+        let message = b"d8:msg_typei1e5:piecei0eeRAW PIECE DATA";
+        let (header, len): (BTreeMap<String, u8>, usize) = from_bytes_prefix(message).unwrap();
+
+        assert_eq!(header.get("msg_type"), Some(&1));
+        assert_eq!(&message[len..], b"RAW PIECE DATA");
+        assert_eq!(from_bytes_prefix::<u8>(b"i1e").unwrap(), (1, 3));
+        assert!(from_bytes_prefix::<u8>(b"i1").is_err());
+    }
+
+    #[test]
+    fn test_stream() {
+        let input = b"i1e3:abcli2eed1:ai3ee";
+        let mut stream = Deserializer::new(input).into_iter::<crate::Value>();
+
+        assert_eq!(stream.next().unwrap().unwrap(), crate::Value::Integer(1));
+        assert_eq!(stream.byte_offset(), 3);
+        assert_eq!(stream.next().unwrap().unwrap(), crate::Value::from("abc"));
+        assert_eq!(stream.by_ref().count(), 2);
+        assert_eq!(stream.byte_offset(), input.len());
+
+        let values = StreamDeserializer::<_, crate::Value>::new(crate::read::IoRead::new(&input[..]))
+            .collect::<crate::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(values.len(), 4);
+        assert_eq!(Deserializer::new(b"").into_iter::<u8>().count(), 0);
+    }
+
+    #[test]
+    fn test_stream_err() {
+        let mut stream = Deserializer::new(b"i1ei2xi3e").into_iter::<u8>();
+
+        assert_eq!(stream.next().unwrap().unwrap(), 1);
+        assert_eq!(stream.next().unwrap().unwrap_err().to_string(), "expected `e` at index 5");
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn test_ignored_any() {
+        #[derive(Deserialize)]
+        struct Torrent {
+            announce: String,
+        }
+
+        let input = b"d8:announce3:url4:infod6:pieces4:\xff\x00\xfe\x013:bigi123456789012345678901234567890123456789eee";
+
+        assert_eq!(from_bytes::<Torrent>(input).unwrap().announce, "url");
+        assert_eq!(from_reader::<_, Torrent>(&input[..]).unwrap().announce, "url");
+        assert!(from_bytes::<serde::de::IgnoredAny>(b"li99999999999999999999999999999999999999999ee").is_ok());
+    }
+
+    #[test]
+    fn test_ignored_any_err() {
+        let a = from_bytes::<serde::de::IgnoredAny>(b"l5:abce");
+        let b = from_reader::<_, serde::de::IgnoredAny>(&b"l5:abce"[..]);
+        let c = from_bytes::<serde::de::IgnoredAny>(b"di1ei2ee");
+        let d = DeserializerOptions::new().max_bytes_len(2).from_bytes::<serde::de::IgnoredAny>(b"3:abc");
+
+        #[derive(Debug, Deserialize)]
+        struct Test { _a: u8 }
+
+        let strict = DeserializerOptions::new().strict(true);
+        let e = strict.from_bytes::<Test>(b"d2:_ai1e1:bd1:zi1e1:yi2eee");
+        let f = strict.from_bytes::<Test>(b"d2:_ai1e1:bd1:xli1ei-0eeee");
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "EOF while parsing at index 7 in [0]");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "EOF while parsing at index 7 in [0]");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "key must be a string at index 1");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "byte string exceeds the size limit at index 0");
+        assert!(e.is_err());
+        assert_eq!(e.unwrap_err().to_string(), "dictionary keys are not sorted at index 18 in b");
+        assert!(f.is_err());
+        assert_eq!(f.unwrap_err().to_string(), "`i-0e` is invalid at index 19 in b.x[1]");
+    }
+
+    #[test]
+    fn test_deserializer_inner() {
+        let mut de = Deserializer::from_reader(&b"i1e3:abcrest"[..]);
+
+        assert_eq!(u8::deserialize(&mut de).unwrap(), 1);
+        assert_eq!(String::deserialize(&mut de).unwrap(), "abc");
+        assert_eq!(de.get_ref().get_ref(), b"rest");
+        assert_eq!(de.into_inner().into_inner(), b"rest");
+    }
+
+    #[test]
+    fn test_deserializer_adapters() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Info {
+            name: String,
+            length: u64,
+        }
+
+        let input = b"d6:lengthi42e4:name1:xe";
+        let info: Info = serde_path_to_error::deserialize(&mut Deserializer::new(input)).unwrap();
+        assert_eq!(info, Info { name: "x".to_owned(), length: 42 });
+
+        let mut de = Deserializer::from_reader(&input[..]);
+        let value: crate::Value = erased_serde::deserialize(&mut <dyn erased_serde::Deserializer>::erase(&mut de)).unwrap();
+        assert_eq!(value.get("length"), Some(&crate::Value::Integer(42)));
+    }
+
+    #[test]
+    fn test_deserializer_adapters_err() {
+        #[derive(Debug, Deserialize)]
+        struct Info {
+            _files: Vec<u8>,
+        }
+
+        let a = serde_path_to_error::deserialize::<_, Info>(&mut Deserializer::new(b"d6:_filesli1e1:xee"));
+        let b = erased_serde::deserialize::<u8>(&mut <dyn erased_serde::Deserializer>::erase(&mut Deserializer::new(b"i300e")));
+
+        assert!(a.is_err());
+        let a = a.unwrap_err();
+        assert_eq!(a.path().to_string(), "_files[1]");
+        assert_eq!(a.inner().to_string(), "expected integer at index 13 in _files[1]");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "integer out of range at index 0");
+    }
 }
@@ -0,0 +1,382 @@
+use std::{fmt, str};
+
+use serde::Deserialize;
+
+use super::de::DeserializerOptions;
+use super::error::{PathSegment, Result};
+use super::raw::RawBencode;
+use super::tokenizer::{Event, Tokenizer};
+
+/// A Bencode document indexed for random access.
+///
+/// Parsing validates the whole input and records, in a single flat tape, the
+/// span of every value along with where its subtree ends, but decodes
+/// nothing. Navigating with `get` and `index` then only walks the tape, and
+/// nodes are deserialized on demand from their own bytes, so looking up
+/// `info.name` in a large torrent never touches the `pieces` blob.
+///
+/// ```
+/// let doc = bencode_rs::Document::parse(b"d4:infod5:filesld6:lengthi42eeeee").unwrap();
+/// let length = doc.get("info")
+///     .and_then(|info| info.get("files"))
+///     .and_then(|files| files.index(0))
+///     .and_then(|file| file.get("length"))
+///     .and_then(|length| length.as_integer());
+///
+/// assert_eq!(length, Some(42));
+/// ```
+#[derive(Debug)]
+pub struct Document<'a> {
+    input: &'a [u8],
+    tape: Vec<Entry>,
+    /// Used again when deserializing nodes.
+    options: DeserializerOptions,
+}
+
+/// A value on the tape. Containers are followed by their elements, and
+/// dictionary elements alternate between keys and values.
+#[derive(Debug)]
+struct Entry {
+    start: usize,
+    end: usize,
+    /// Tape position right after this value's subtree.
+    next: usize,
+    /// Number of elements for lists, of entries for dictionaries and of
+    /// content bytes for byte strings.
+    len: usize,
+}
+
+/// Kind of value a `Node` refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Integer,
+    Bytes,
+    List,
+    Dict,
+}
+
+/// A value inside a `Document`.
+#[derive(Clone, Copy)]
+pub struct Node<'d, 'a> {
+    doc: &'d Document<'a>,
+    pos: usize,
+}
+
+impl<'a> Document<'a> {
+    pub fn parse(input: &'a [u8]) -> Result<Self> {
+        Document::parse_with_options(input, DeserializerOptions::default())
+    }
+
+    /// Parses `input` with the validation rules and limits of `options`.
+    pub fn parse_with_options(input: &'a [u8], options: DeserializerOptions) -> Result<Self> {
+        let mut tokenizer = Tokenizer::with_options(input, options);
+        let mut tape = Vec::new();
+        let mut open = Vec::new();
+
+        while let Some(event) = tokenizer.next() {
+            let (start, event) = event?;
+            let end = tokenizer.byte_offset();
+
+            match event {
+                Event::ListStart | Event::DictStart => {
+                    open.push(tape.len());
+                    tape.push(Entry { start, end, next: 0, len: 0 });
+                },
+                Event::End => {
+                    let pos = open.pop().expect("tokenizer balances containers");
+                    let mut len = 0;
+                    let mut child = pos + 1;
+                    while child < tape.len() {
+                        child = tape[child].next;
+                        len += 1;
+                    }
+                    if input[tape[pos].start] == b'd' {
+                        len /= 2;
+                    }
+
+                    let next = tape.len();
+                    let entry = &mut tape[pos];
+                    entry.end = end;
+                    entry.next = next;
+                    entry.len = len;
+                },
+                Event::Bytes(bytes) => {
+                    let next = tape.len() + 1;
+                    tape.push(Entry { start, end, next, len: bytes.len() });
+                },
+                Event::Int(_) | Event::BigInt(_) => {
+                    let next = tape.len() + 1;
+                    tape.push(Entry { start, end, next, len: 0 });
+                },
+            }
+        }
+
+        Ok(Document { input, tape, options })
+    }
+
+    pub fn root(&self) -> Node<'_, 'a> {
+        Node { doc: self, pos: 0 }
+    }
+
+    /// Looks up `key` if the root is a dictionary.
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<Node<'_, 'a>> {
+        self.root().get(key)
+    }
+
+    /// Returns the element at `index` if the root is a list.
+    pub fn index(&self, index: usize) -> Option<Node<'_, 'a>> {
+        self.root().index(index)
+    }
+}
+
+impl<'d, 'a> Node<'d, 'a> {
+    fn entry(&self) -> &'d Entry {
+        &self.doc.tape[self.pos]
+    }
+
+    pub fn kind(&self) -> NodeKind {
+        match self.doc.input[self.entry().start] {
+            b'i' => NodeKind::Integer,
+            b'l' => NodeKind::List,
+            b'd' => NodeKind::Dict,
+            _ => NodeKind::Bytes,
+        }
+    }
+
+    /// Offset of the value in the input.
+    pub fn offset(&self) -> usize {
+        self.entry().start
+    }
+
+    /// The encoded bytes of the value.
+    pub fn raw(&self) -> RawBencode<'a> {
+        let entry = self.entry();
+        RawBencode::new(&self.doc.input[entry.start..entry.end])
+    }
+
+    /// Number of elements of a list or entries of a dictionary, and `0` for
+    /// other values.
+    pub fn len(&self) -> usize {
+        match self.kind() {
+            NodeKind::List | NodeKind::Dict => self.entry().len,
+            NodeKind::Integer | NodeKind::Bytes => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the integer if it fits in an `i64`.
+    pub fn as_integer(&self) -> Option<i64> {
+        match self.kind() {
+            NodeKind::Integer => self.deserialize().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self.kind() {
+            NodeKind::Bytes => {
+                let entry = self.entry();
+                Some(&self.doc.input[entry.end - entry.len..entry.end])
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the byte string as a `&str` if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        self.as_bytes().and_then(|b| str::from_utf8(b).ok())
+    }
+
+    /// Looks up `key` if this value is a dictionary.
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<Node<'d, 'a>> {
+        self.entries()?
+            .find(|(k, _)| *k == key.as_ref())
+            .map(|(_, v)| v)
+    }
+
+    /// Returns the element at `index` if this value is a list.
+    pub fn index(&self, index: usize) -> Option<Node<'d, 'a>> {
+        self.elements()?.nth(index)
+    }
+
+    /// Iterates over the elements if this value is a list.
+    pub fn elements(&self) -> Option<impl Iterator<Item = Node<'d, 'a>>> {
+        match self.kind() {
+            NodeKind::List => Some(self.children()),
+            _ => None,
+        }
+    }
+
+    /// Iterates over the keys and values, in input order, if this value is a
+    /// dictionary.
+    pub fn entries(&self) -> Option<impl Iterator<Item = (&'a [u8], Node<'d, 'a>)>> {
+        match self.kind() {
+            NodeKind::Dict => {
+                let mut children = self.children();
+                Some(std::iter::from_fn(move || {
+                    let key = children.next()?.as_bytes().expect("dictionary keys are byte strings");
+                    Some((key, children.next()?))
+                }))
+            },
+            _ => None,
+        }
+    }
+
+    fn children(&self) -> impl Iterator<Item = Node<'d, 'a>> {
+        let doc = self.doc;
+        let end = self.entry().next;
+        let mut pos = self.pos + 1;
+        std::iter::from_fn(move || {
+            if pos >= end {
+                return None;
+            }
+            let node = Node { doc, pos };
+            pos = doc.tape[pos].next;
+            Some(node)
+        })
+    }
+
+    /// Segments leading from the root to this value.
+    fn path(&self) -> Vec<PathSegment> {
+        let mut path = Vec::new();
+        let mut node = self.doc.root();
+        let contains = |child: &Node| child.pos <= self.pos && self.pos < child.entry().next;
+
+        while node.pos != self.pos {
+            let next = match node.kind() {
+                NodeKind::List => node.children()
+                    .enumerate()
+                    .find(|(_, child)| contains(child))
+                    .map(|(i, child)| (PathSegment::Index(i), child)),
+                NodeKind::Dict => node.entries()
+                    .and_then(|mut entries| entries.find(|(_, child)| contains(child)))
+                    .map(|(key, child)| (PathSegment::Key(key.to_vec()), child)),
+                NodeKind::Integer | NodeKind::Bytes => None,
+            };
+            // Dictionary keys are not reached through a path.
+            let Some((segment, child)) = next else { break };
+            path.push(segment);
+            node = child;
+        }
+
+        path
+    }
+
+    /// Deserializes the value from its encoded bytes, with the options the
+    /// document was parsed with. Errors are reported relative to the whole
+    /// document.
+    pub fn deserialize<T>(&self) -> Result<T>
+    where
+        T: Deserialize<'a>,
+    {
+        self.doc.options.from_bytes(self.raw().as_bytes()).map_err(|e| {
+            self.path()
+                .into_iter()
+                .rev()
+                .fold(e.shifted(self.offset()), |e, segment| e.nested(segment, None))
+        })
+    }
+}
+
+impl fmt::Debug for Node<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("kind", &self.kind())
+            .field("offset", &self.offset())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use crate::{BoolPolicy, DeserializerOptions, Value};
+    use super::{Document, NodeKind};
+
+    const TORRENT: &[u8] = b"d8:announce3:url4:infod5:filesld6:lengthi1e4:pathl1:aeed6:lengthi2e4:pathl1:beee4:name1:x6:pieces2:abee";
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct File<'a> {
+        length: u64,
+        #[serde(borrow)]
+        path: Vec<&'a str>,
+    }
+
+    #[test]
+    fn test_document() {
+        let doc = Document::parse(TORRENT).unwrap();
+        let info = doc.get("info").unwrap();
+        let files = info.get(b"files").unwrap();
+
+        assert_eq!(doc.root().kind(), NodeKind::Dict);
+        assert_eq!(doc.root().len(), 2);
+        assert_eq!(doc.get("announce").and_then(|a| a.as_str()), Some("url"));
+        assert_eq!(files.kind(), NodeKind::List);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files.index(1).and_then(|f| f.get("length")).and_then(|l| l.as_integer()), Some(2));
+        assert_eq!(files.index(0).unwrap().deserialize::<File>().unwrap(), File { length: 1, path: vec!["a"] });
+        assert_eq!(info.entries().unwrap().map(|(k, _)| k).collect::<Vec<_>>(), [&b"files"[..], b"name", b"pieces"]);
+        assert_eq!(info.get("pieces").unwrap().raw().as_bytes(), b"2:ab");
+        assert_eq!(info.get("pieces").unwrap().offset(), 97);
+        assert!(doc.get("missing").is_none());
+        assert!(files.index(2).is_none());
+        assert!(files.get("length").is_none());
+        assert!(info.index(0).is_none());
+    }
+
+    #[test]
+    fn test_document_scalars() {
+        let doc = Document::parse(b"li-1ei99999999999999999999e0:ledee").unwrap();
+        let elements = doc.root().elements().unwrap().collect::<Vec<_>>();
+
+        assert_eq!(elements.len(), 5);
+        assert_eq!(elements[0].as_integer(), Some(-1));
+        assert_eq!(elements[1].kind(), NodeKind::Integer);
+        assert_eq!(elements[1].as_integer(), None);
+        assert_eq!(elements[2].as_bytes(), Some(&b""[..]));
+        assert!(elements[3].is_empty());
+        assert_eq!(elements[4].deserialize::<Value>().unwrap(), Value::Dict(Default::default()));
+        assert_eq!(doc.index(3).map(|l| l.kind()), Some(NodeKind::List));
+    }
+
+    #[test]
+    fn test_document_options() {
+        let options = DeserializerOptions::new().bool_policy(BoolPolicy::NonZero);
+        let doc = Document::parse_with_options(b"d1:ali5ei0eee", options).unwrap();
+        let a = Document::parse(b"d1:ali5ei0eee").unwrap().get("a").unwrap().deserialize::<Vec<bool>>();
+
+        assert_eq!(doc.get("a").unwrap().deserialize::<Vec<bool>>().unwrap(), [true, false]);
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "expected boolean at index 5 in a[0]");
+
+        let doc = Document::parse(TORRENT).unwrap();
+        let files = doc.get("info").and_then(|info| info.get("files")).unwrap();
+        let b = files.index(1).unwrap().deserialize::<BTreeMap<String, Vec<u8>>>();
+
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "expected list at index 64 in info.files[1].length");
+    }
+
+    #[test]
+    fn test_document_err() {
+        let a = Document::parse(b"d4:infod5:filesle");
+        let b = Document::parse(b"li1ei2eei3e");
+        let c = Document::parse_with_options(b"d1:bi1e1:ai2ee", DeserializerOptions::new().strict(true));
+        let d = Document::parse(b"");
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "EOF while parsing at index 17");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "trailing characters at index 8");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "dictionary keys are not sorted at index 7");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "EOF while parsing at index 0");
+    }
+}
@@ -1,4 +1,4 @@
-use std::{fmt, io, str};
+use std::{fmt, io, mem, str};
 use serde::{de, ser};
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -16,11 +16,81 @@ impl Error {
     pub fn index(&self) -> Option<usize> {
         self.err.index
     }
-    
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.err.kind
+    }
+
+    /// Categorizes the cause of this error.
+    pub fn classify(&self) -> Category {
+        self.err.kind.classify()
+    }
+
+    pub fn is_io(&self) -> bool {
+        self.classify() == Category::Io
+    }
+
+    pub fn is_syntax(&self) -> bool {
+        self.classify() == Category::Syntax
+    }
+
+    pub fn is_data(&self) -> bool {
+        self.classify() == Category::Data
+    }
+
+    pub fn is_eof(&self) -> bool {
+        self.classify() == Category::Eof
+    }
+
+    pub fn is_limit(&self) -> bool {
+        self.classify() == Category::Limit
+    }
+
+    /// Location of the failing value within the document, as the dictionary
+    /// keys and list indices leading to it. Empty for errors at the top level.
+    pub fn path(&self) -> &Path {
+        &self.err.path
+    }
+
+    /// Renders the error along with a hex dump of `input` around its index,
+    /// pointing at the offending byte, and a description of the enclosing
+    /// structure. `input` must be the document the error comes from.
+    pub fn snippet<'a>(&'a self, input: &'a [u8]) -> Snippet<'a> {
+        Snippet { err: &self.err, input }
+    }
+
+    /// Records that the error happened inside the value at `segment`, which
+    /// starts at `index`. The index is only used if the error has none.
+    #[cold]
+    pub(in crate) fn nested(mut self, segment: PathSegment, index: Option<usize>) -> Self {
+        self.err.path.segments.insert(0, segment);
+        if self.err.index.is_none() {
+            self.err.index = index;
+        }
+        self
+    }
+
+    /// Moves the error's index `offset` bytes forward, for errors found in
+    /// a value decoded apart from the input holding it.
+    #[cold]
+    pub(in crate) fn shifted(mut self, offset: usize) -> Self {
+        if let Some(index) = self.err.index.as_mut() {
+            *index += offset;
+        }
+        self
+    }
+
     #[cold]
     pub(in crate) fn syntax(kind: ErrorKind, index: usize) -> Self {
         Error {
-            err: Box::new(ErrorContent { kind, index: Some(index) })
+            err: Box::new(ErrorContent { kind, index: Some(index), path: Path::default() })
+        }
+    }
+
+    #[cold]
+    pub(in crate) fn data(kind: ErrorKind) -> Self {
+        Error {
+            err: Box::new(ErrorContent { kind, index: None, path: Path::default() })
         }
     }
 
@@ -29,6 +99,7 @@ impl Error {
         Error { err: Box::new(ErrorContent {
             kind: ErrorKind::Eof,
             index: Some(index),
+            path: Path::default(),
         })}
     }
 
@@ -37,6 +108,7 @@ impl Error {
         Error { err: Box::new(ErrorContent {
             kind: ErrorKind::Io(err),
             index: None,
+            path: Path::default(),
         })}
     }
 }
@@ -49,12 +121,14 @@ impl fmt::Display for Error {
 
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "Error({:?}, index: {})",
-            self.err.kind.to_string(),
-            self.err.index.unwrap_or(0),
-        )
+        write!(f, "Error({:?}", self.err.kind.to_string())?;
+        if let Some(index) = self.err.index {
+            write!(f, ", index: {}", index)?;
+        }
+        if !self.err.path.is_empty() {
+            write!(f, ", path: {:?}", self.err.path.to_string())?;
+        }
+        f.write_str(")")
     }
 }
 
@@ -74,6 +148,7 @@ impl ser::Error for Error {
             err: Box::new(ErrorContent {
                 kind: ErrorKind::Message(msg.to_string().into_boxed_str()),
                 index: None,
+                path: Path::default(),
             })
         }
     }
@@ -86,6 +161,7 @@ impl de::Error for Error {
             err: Box::new(ErrorContent {
                 kind: ErrorKind::Message(msg.to_string().into_boxed_str()),
                 index: None,
+                path: Path::default(),
             })
         }
     }
@@ -100,18 +176,171 @@ impl From<io::Error> for Error {
 struct ErrorContent {
     kind: ErrorKind,
     index: Option<usize>,
+    path: Path,
 }
 
 impl fmt::Display for ErrorContent {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if self.index.is_none() {
-            fmt::Display::fmt(&self.kind, f)
-        } else {
-            write!(f, "{} at index {}", self.kind, self.index.unwrap())
+        fmt::Display::fmt(&self.kind, f)?;
+        if let Some(index) = self.index {
+            write!(f, " at index {}", index)?;
+        }
+        if !self.path.is_empty() {
+            write!(f, " in {}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Error rendering returned by `Error::snippet`, e.g.
+///
+/// ```text
+/// integer out of range at index 49
+/// inside dict key `info`, dict key `files`, list element 1, dict key `length`
+/// 00000020  74 68 6c 31 3a 61 65 65 64 36 3a 6c 65 6e 67 74  |thl1:aeed6:lengt|
+/// 00000030  68 69 2d 32 65 34 3a 70 61 74 68 6c 31 3a 62 65  |hi-2e4:pathl1:be|
+///              ^^                                              ^
+/// 00000040  65 65 65 65                                      |eeee|
+/// ```
+pub struct Snippet<'a> {
+    err: &'a ErrorContent,
+    input: &'a [u8],
+}
+
+/// Number of bytes per row of the hex dump.
+const SNIPPET_WIDTH: usize = 16;
+
+impl fmt::Display for Snippet<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.err.kind, f)?;
+        let index = match self.err.index {
+            Some(index) => index,
+            None => return fmt::Display::fmt(&self.err.path.described(), f),
+        };
+        write!(f, " at index {}", index)?;
+        fmt::Display::fmt(&self.err.path.described(), f)?;
+
+        let row = index / SNIPPET_WIDTH;
+        let last_row = self.input.len().saturating_sub(1) / SNIPPET_WIDTH;
+        for r in row.saturating_sub(1)..=(row + 1).min(last_row.max(row)) {
+            let start = (r * SNIPPET_WIDTH).min(self.input.len());
+            let bytes = &self.input[start..(start + SNIPPET_WIDTH).min(self.input.len())];
+
+            write!(f, "\n{:08x}  ", r * SNIPPET_WIDTH)?;
+            for i in 0..SNIPPET_WIDTH {
+                match bytes.get(i) {
+                    Some(b) => write!(f, "{:02x} ", b)?,
+                    None => f.write_str("   ")?,
+                }
+            }
+            f.write_str(" |")?;
+            for &b in bytes {
+                let c = if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' };
+                write!(f, "{}", c)?;
+            }
+            f.write_str("|")?;
+
+            if r == row {
+                let col = index % SNIPPET_WIDTH;
+                let hex_col = 10 + col * 3;
+                let ascii_col = 10 + SNIPPET_WIDTH * 3 + 2 + col;
+                write!(f, "\n{:hex_col$}^^{:gap$}^", "", "", gap = ascii_col - hex_col - 2)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Path from the root of a document to one of its values, displayed as
+/// `info.files[312].length`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Path {
+    segments: Vec<PathSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// Dictionary key, as raw bytes.
+    Key(Vec<u8>),
+    /// List index.
+    Index(usize),
+}
+
+impl Path {
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Describes the path in words, on its own line, for `Snippet`.
+    fn described(&self) -> impl fmt::Display + '_ {
+        struct Described<'a>(&'a Path);
+
+        impl fmt::Display for Described<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                for (i, segment) in self.0.segments.iter().enumerate() {
+                    f.write_str(if i == 0 { "\ninside " } else { ", " })?;
+                    match segment {
+                        PathSegment::Key(key) => write!(f, "dict key `{}`", key.escape_ascii())?,
+                        PathSegment::Index(index) => write!(f, "list element {}", index)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        Described(self)
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                PathSegment::Key(key) if is_identifier(key) => {
+                    if i > 0 {
+                        f.write_str(".")?;
+                    }
+                    // Identifiers are ASCII.
+                    f.write_str(str::from_utf8(key).unwrap_or_default())?;
+                },
+                PathSegment::Key(key) => write!(f, "[\"{}\"]", key.escape_ascii())?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
         }
+        Ok(())
     }
 }
 
+/// Keys that can be displayed after a dot without ambiguity.
+fn is_identifier(key: &[u8]) -> bool {
+    !key.is_empty() && key.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-' || b == b' ')
+}
+
+/// Broad cause of an `Error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// The input is not valid Bencode.
+    Syntax,
+    /// The input ended before a complete value was read.
+    Eof,
+    /// Reading or writing the underlying I/O stream failed.
+    Io,
+    /// The input is valid Bencode, but does not match the type it is decoded
+    /// into, or a value cannot be encoded.
+    Data,
+    /// One of the configured resource limits was exceeded.
+    Limit,
+}
+
+/// Precise cause of an `Error`.
+///
+/// I/O errors compare equal when their `io::ErrorKind` is the same.
+#[derive(Debug)]
+#[non_exhaustive]
 pub enum ErrorKind {
     Message(Box<str>),
     Io(io::Error),
@@ -129,11 +358,50 @@ pub enum ErrorKind {
     MinusZero,
     LeadingZero,
     IntegerOutOfRange,
+    ExpectedFloat,
+    FloatNotRepresentable,
     StringNotUtf8,
     KeyMustBeAString,
+    UnsortedKeys,
+    DuplicateKey,
+    RecursionLimitExceeded,
+    BytesTooLong,
+    ListTooLong,
+    DictTooLong,
+    InputTooLong,
     TrailingCharacters,
 }
 
+impl ErrorKind {
+    pub fn classify(&self) -> Category {
+        use self::ErrorKind::*;
+        match *self {
+            Message(_) | ExpectedBoolean | ExpectedChar | IntegerOutOfRange
+            | ExpectedFloat | FloatNotRepresentable | StringNotUtf8
+            | KeyMustBeAString => Category::Data,
+            Io(_) => Category::Io,
+            Eof => Category::Eof,
+            RecursionLimitExceeded | BytesTooLong | ListTooLong | DictTooLong
+            | InputTooLong => Category::Limit,
+            ExpectedInteger | ExpectedString | ExpectedList | ExpectedDict
+            | ExpectedStringDelim | ExpectedEnum | ExpectedEnd | ExpectedSomeValue
+            | MinusZero | LeadingZero | UnsortedKeys | DuplicateKey
+            | TrailingCharacters => Category::Syntax,
+        }
+    }
+}
+
+impl PartialEq for ErrorKind {
+    fn eq(&self, other: &Self) -> bool {
+        use self::ErrorKind::*;
+        match (self, other) {
+            (Message(a), Message(b)) => a == b,
+            (Io(a), Io(b)) => a.kind() == b.kind(),
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+}
+
 impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use self::ErrorKind::*;
@@ -154,9 +422,118 @@ impl fmt::Display for ErrorKind {
             MinusZero           => write!(f, "`i-0e` is invalid"),
             LeadingZero         => write!(f, "leading zeros are invalid"),
             IntegerOutOfRange   => write!(f, "integer out of range"),
+            ExpectedFloat       => write!(f, "expected float"),
+            FloatNotRepresentable => write!(f, "float cannot be represented"),
             StringNotUtf8       => write!(f, "strings must be a utf-8"),
             KeyMustBeAString    => write!(f, "key must be a string"),
+            UnsortedKeys        => write!(f, "dictionary keys are not sorted"),
+            DuplicateKey        => write!(f, "duplicate dictionary key"),
+            RecursionLimitExceeded => write!(f, "recursion limit exceeded"),
+            BytesTooLong        => write!(f, "byte string exceeds the size limit"),
+            ListTooLong         => write!(f, "list exceeds the size limit"),
+            DictTooLong         => write!(f, "dictionary exceeds the size limit"),
+            InputTooLong        => write!(f, "input exceeds the size limit"),
             TrailingCharacters  => write!(f, "trailing characters"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, io};
+
+    use serde::Deserialize;
+
+    use crate::{from_bytes, from_reader, to_bytes, DeserializerOptions, Value};
+    use super::{Category, ErrorKind};
+
+    #[test]
+    fn test_kind() {
+        let syntax = from_bytes::<Value>(b"i01e").unwrap_err();
+        let eof = from_bytes::<Value>(b"l").unwrap_err();
+        let data = from_bytes::<u8>(b"i300e").unwrap_err();
+        let message = from_bytes::<(u8, u8)>(b"li1ee").unwrap_err();
+        let limit = DeserializerOptions::new().max_bytes_len(1).from_bytes::<Value>(b"2:ab").unwrap_err();
+        let io = from_reader::<_, Value>(Failing).unwrap_err();
+        let ser = to_bytes(&BTreeMap::from([(1, 2)])).unwrap_err();
+
+        assert_eq!(*syntax.kind(), ErrorKind::LeadingZero);
+        assert_eq!(syntax.classify(), Category::Syntax);
+        assert!(syntax.is_syntax());
+        assert_eq!(*eof.kind(), ErrorKind::Eof);
+        assert!(eof.is_eof());
+        assert_eq!(*data.kind(), ErrorKind::IntegerOutOfRange);
+        assert!(data.is_data());
+        assert!(matches!(message.kind(), ErrorKind::Message(_)));
+        assert!(message.is_data());
+        assert_eq!(*limit.kind(), ErrorKind::BytesTooLong);
+        assert!(limit.is_limit());
+        assert_eq!(*io.kind(), ErrorKind::Io(io::Error::other("other message")));
+        assert!(io.is_io());
+        assert_eq!(*ser.kind(), ErrorKind::KeyMustBeAString);
+        assert_ne!(*ser.kind(), ErrorKind::StringNotUtf8);
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct File {
+        length: u64,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Info {
+        files: Vec<File>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Torrent {
+        info: Info,
+    }
+
+    #[test]
+    fn test_debug() {
+        let a = from_bytes::<Torrent>(b"d4:infod5:filesld6:lengthi-2eeeee").unwrap_err();
+        let b = from_bytes::<(u8, u8)>(b"li1ee").unwrap_err();
+
+        assert_eq!(format!("{:?}", a), "Error(\"integer out of range\", index: 25, path: \"info.files[0].length\")");
+        assert_eq!(format!("{:?}", b), "Error(\"invalid length 1, expected a tuple of size 2\")");
+    }
+
+    #[test]
+    fn test_snippet() {
+        let input = b"d4:infod5:filesld6:lengthi1e4:pathl1:aeed6:lengthi-2e4:pathl1:beeeee";
+        let a = from_bytes::<BTreeMap<String, BTreeMap<String, Vec<BTreeMap<String, Value>>>>>(b"d4:infod5:filesli1eeee").unwrap_err();
+        let b = from_bytes::<Value>(b"li1e").unwrap_err();
+        let c = from_bytes::<(u8, u8)>(b"li1ee").unwrap_err();
+        let d = from_bytes::<Torrent>(input).unwrap_err();
+
+        assert_eq!(a.snippet(b"d4:infod5:filesli1eeee").to_string(), "\
+expected dictionary at index 16
+inside dict key `info`, dict key `files`, list element 0
+00000000  64 34 3a 69 6e 66 6f 64 35 3a 66 69 6c 65 73 6c  |d4:infod5:filesl|
+00000010  69 31 65 65 65 65                                |i1eeee|
+          ^^                                                ^");
+        assert_eq!(b.snippet(b"li1e").to_string(), "\
+EOF while parsing at index 4
+00000000  6c 69 31 65                                      |li1e|
+                      ^^                                        ^");
+        assert_eq!(c.snippet(b"li1ee").to_string(), "invalid length 1, expected a tuple of size 2");
+        assert_eq!(d.snippet(input).to_string(), "\
+integer out of range at index 49
+inside dict key `info`, dict key `files`, list element 1, dict key `length`
+00000020  74 68 6c 31 3a 61 65 65 64 36 3a 6c 65 6e 67 74  |thl1:aeed6:lengt|
+00000030  68 69 2d 32 65 34 3a 70 61 74 68 6c 31 3a 62 65  |hi-2e4:pathl1:be|
+             ^^                                              ^
+00000040  65 65 65 65                                      |eeee|");
+    }
+
+    struct Failing;
+
+    impl io::Read for Failing {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("failing reader"))
+        }
+    }
+}
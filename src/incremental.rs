@@ -0,0 +1,446 @@
+use super::de::DeserializerOptions;
+use super::error::{Error, ErrorKind, Result};
+
+/// Outcome of feeding bytes to an `IncrementalParser`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// The value is not complete yet, more bytes are needed.
+    NeedMore,
+    /// A complete value spans this many bytes, counted from the first byte
+    /// fed since the value started.
+    Complete(usize),
+}
+
+/// Push-style parser finding where Bencode values end in a stream received
+/// in arbitrary fragments, e.g. to frame peer-wire or DHT messages.
+///
+/// Every byte is inspected only once, whatever the fragmentation. The parser
+/// validates the syntax the same way the `Deserializer` does and enforces the
+/// strictness, recursion and size limits of its `DeserializerOptions`, but
+/// does not decode anything: once a value is complete, its bytes can be handed to
+/// `from_bytes`.
+///
+/// Error indices are relative to the start of the current value. After an
+/// error the stream cannot be resynchronized, `reset` must be called before
+/// feeding another value.
+#[derive(Debug, Clone)]
+pub struct IncrementalParser {
+    options: DeserializerOptions,
+    /// Bytes of the current value consumed so far.
+    len: usize,
+    stack: Vec<Container>,
+    token: Token,
+    /// Start and bytes so far of the dictionary key being read, in strict
+    /// mode only.
+    key: Option<(usize, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone)]
+struct Container {
+    kind: ContainerKind,
+    /// Number of elements, or entries for dictionaries.
+    count: usize,
+    /// Previous key of a dictionary, in strict mode only.
+    last_key: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ContainerKind {
+    List,
+    DictKey,
+    DictValue,
+}
+
+/// Token being read when a fragment ends in the middle of it.
+#[derive(Debug, Clone, Copy)]
+enum Token {
+    /// Between two tokens.
+    None,
+    Integer {
+        start: usize,
+        state: IntegerState,
+    },
+    Length {
+        start: usize,
+        /// `None` once the length overflowed, which is only reported at the
+        /// delimiter like the `Deserializer` does.
+        len: Option<usize>,
+        leading_zero: bool,
+    },
+    Bytes {
+        remaining: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IntegerState {
+    /// Right after `i`.
+    Start,
+    /// After `i-`.
+    Minus,
+    /// After `i-0`.
+    MinusZero,
+    /// After `i0`.
+    Zero,
+    Digits,
+}
+
+impl Default for IncrementalParser {
+    fn default() -> Self {
+        IncrementalParser::new()
+    }
+}
+
+impl IncrementalParser {
+    pub fn new() -> Self {
+        IncrementalParser::with_options(DeserializerOptions::default())
+    }
+
+    pub fn with_options(options: DeserializerOptions) -> Self {
+        IncrementalParser {
+            options,
+            len: 0,
+            stack: Vec::new(),
+            token: Token::None,
+            key: None,
+        }
+    }
+
+    /// Number of bytes of the current value consumed so far.
+    pub fn consumed(&self) -> usize {
+        self.len
+    }
+
+    /// Forgets the current value, to start over at the beginning of a new one.
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.stack.clear();
+        self.token = Token::None;
+        self.key = None;
+    }
+
+    /// Scans the next fragment of input.
+    ///
+    /// When a value completes within `chunk`, the bytes of `chunk` following
+    /// it are left unread and the parser is ready for the next value: they
+    /// should be fed again.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Progress> {
+        let mut i = 0;
+
+        while i < chunk.len() {
+            if let Token::Bytes { remaining } = self.token {
+                let n = remaining.min(chunk.len() - i);
+                if let Some((_, key)) = &mut self.key {
+                    key.extend_from_slice(&chunk[i..i + n]);
+                }
+                self.len += n;
+                i += n;
+                if n < remaining {
+                    self.token = Token::Bytes { remaining: remaining - n };
+                    break;
+                }
+                self.token = Token::None;
+                if let Some(len) = self.end_value()? {
+                    return Ok(Progress::Complete(len));
+                }
+                continue;
+            }
+
+            if self.len >= self.options.max_input_len {
+                return Err(Error::syntax(ErrorKind::InputTooLong, self.options.max_input_len));
+            }
+
+            let index = self.len;
+            let b = chunk[i];
+            self.len += 1;
+            i += 1;
+
+            let complete = match self.token {
+                Token::None => self.scan_start(b, index)?,
+                Token::Integer { start, state } => self.scan_integer(b, index, start, state)?,
+                Token::Length { start, len, leading_zero } => self.scan_length(b, index, start, len, leading_zero)?,
+                Token::Bytes { .. } => unreachable!(),
+            };
+            if complete {
+                if let Some(len) = self.end_value()? {
+                    return Ok(Progress::Complete(len));
+                }
+            }
+        }
+
+        // A zero-length string is complete as soon as its delimiter is read.
+        if let Token::Bytes { remaining: 0 } = self.token {
+            self.token = Token::None;
+            if let Some(len) = self.end_value()? {
+                return Ok(Progress::Complete(len));
+            }
+        }
+
+        Ok(Progress::NeedMore)
+    }
+
+    /// Handles the first byte of a token, returns whether it completed a
+    /// value.
+    fn scan_start(&mut self, b: u8, index: usize) -> Result<bool> {
+        let top = self.stack.last().map(|c| c.kind);
+
+        if b == b'e' {
+            return match top {
+                Some(ContainerKind::List) | Some(ContainerKind::DictKey) => {
+                    self.stack.pop();
+                    Ok(true)
+                },
+                _ => Err(Error::syntax(ErrorKind::ExpectedSomeValue, index)),
+            };
+        }
+
+        match (top, b) {
+            (Some(ContainerKind::DictKey), b'i' | b'l' | b'd') => {
+                return Err(Error::syntax(ErrorKind::KeyMustBeAString, index));
+            },
+            (Some(ContainerKind::DictKey), b'0'..=b'9') => {
+                self.count_element(index)?;
+                if self.options.strict {
+                    self.key = Some((index, Vec::new()));
+                }
+            },
+            (Some(ContainerKind::List), b'i' | b'l' | b'd' | b'0'..=b'9') => self.count_element(index)?,
+            (Some(ContainerKind::DictKey | ContainerKind::List), _) => {
+                return Err(Error::syntax(ErrorKind::ExpectedEnd, index));
+            },
+            _ => {},
+        }
+
+        match b {
+            b'i' => self.token = Token::Integer { start: index, state: IntegerState::Start },
+            b'0'..=b'9' => {
+                self.token = Token::Length {
+                    start: index,
+                    len: Some((b - b'0') as usize),
+                    leading_zero: b == b'0',
+                };
+            },
+            b'l' | b'd' => {
+                if self.stack.len() >= self.options.recursion_limit {
+                    return Err(Error::syntax(ErrorKind::RecursionLimitExceeded, index));
+                }
+                let kind = if b == b'l' { ContainerKind::List } else { ContainerKind::DictKey };
+                self.stack.push(Container { kind, count: 0, last_key: None });
+            },
+            _ => return Err(Error::syntax(ErrorKind::ExpectedSomeValue, index)),
+        }
+
+        Ok(false)
+    }
+
+    fn scan_integer(&mut self, b: u8, index: usize, start: usize, state: IntegerState) -> Result<bool> {
+        let state = match (state, b) {
+            (IntegerState::Start, b'-') => IntegerState::Minus,
+            (IntegerState::Start, b'0') => IntegerState::Zero,
+            (IntegerState::Minus, b'0') => IntegerState::MinusZero,
+            (IntegerState::Start | IntegerState::Minus | IntegerState::Digits, b'1'..=b'9')
+            | (IntegerState::Digits, b'0') => IntegerState::Digits,
+            (IntegerState::MinusZero, b'e') => return Err(Error::syntax(ErrorKind::MinusZero, start)),
+            // Reported at the zero, like the `Deserializer` does.
+            (IntegerState::Zero | IntegerState::MinusZero, b'0'..=b'9') => {
+                return Err(Error::syntax(ErrorKind::LeadingZero, index - 1));
+            },
+            (IntegerState::Zero | IntegerState::Digits, b'e') => {
+                self.token = Token::None;
+                return Ok(true);
+            },
+            (IntegerState::Start | IntegerState::Minus | IntegerState::MinusZero, _) => {
+                return Err(Error::syntax(ErrorKind::ExpectedInteger, start));
+            },
+            (IntegerState::Zero | IntegerState::Digits, _) => {
+                return Err(Error::syntax(ErrorKind::ExpectedEnd, index));
+            },
+        };
+
+        self.token = Token::Integer { start, state };
+        Ok(false)
+    }
+
+    fn scan_length(
+        &mut self,
+        b: u8,
+        index: usize,
+        start: usize,
+        len: Option<usize>,
+        leading_zero: bool,
+    ) -> Result<bool> {
+        match b {
+            b'0'..=b'9' if leading_zero => Err(Error::syntax(ErrorKind::LeadingZero, index - 1)),
+            b'0'..=b'9' => {
+                let len = len
+                    .and_then(|n| n.checked_mul(10))
+                    .and_then(|n| n.checked_add((b - b'0') as usize));
+                self.token = Token::Length { start, len, leading_zero };
+                Ok(false)
+            },
+            b':' => {
+                let len = len.ok_or_else(|| Error::syntax(ErrorKind::IntegerOutOfRange, start))?;
+                if len > self.options.max_bytes_len {
+                    return Err(Error::syntax(ErrorKind::BytesTooLong, start));
+                }
+                if len > self.options.max_input_len.saturating_sub(self.len) {
+                    return Err(Error::syntax(ErrorKind::InputTooLong, self.options.max_input_len));
+                }
+                self.token = Token::Bytes { remaining: len };
+                Ok(false)
+            },
+            _ => Err(Error::syntax(ErrorKind::ExpectedStringDelim, index)),
+        }
+    }
+
+    fn count_element(&mut self, index: usize) -> Result<()> {
+        let (max, kind) = match self.stack.last().map(|c| c.kind) {
+            Some(ContainerKind::List) => (self.options.max_list_len, ErrorKind::ListTooLong),
+            _ => (self.options.max_dict_len, ErrorKind::DictTooLong),
+        };
+        let container = self.stack.last_mut().expect("element outside of a container");
+        if container.count >= max {
+            return Err(Error::syntax(kind, index));
+        }
+        container.count += 1;
+        Ok(())
+    }
+
+    /// Called after a complete value was read. Returns the length of the
+    /// top-level value if it was the one completed, and resets the parser
+    /// for the next one.
+    fn end_value(&mut self) -> Result<Option<usize>> {
+        let key = self.key.take();
+        match self.stack.last_mut() {
+            None => {
+                let len = self.len;
+                self.reset();
+                Ok(Some(len))
+            },
+            Some(container) => {
+                if let Some((start, key)) = key {
+                    match container.last_key {
+                        Some(ref last) if key < *last => return Err(Error::syntax(ErrorKind::UnsortedKeys, start)),
+                        Some(ref last) if key == *last => return Err(Error::syntax(ErrorKind::DuplicateKey, start)),
+                        _ => {},
+                    }
+                    container.last_key = Some(key);
+                }
+                container.kind = match container.kind {
+                    ContainerKind::DictKey => ContainerKind::DictValue,
+                    ContainerKind::DictValue => ContainerKind::DictKey,
+                    ContainerKind::List => ContainerKind::List,
+                };
+                Ok(None)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{from_bytes, DeserializerOptions, RawBencode, Value};
+    use super::{IncrementalParser, Progress};
+
+    const TORRENT: &[u8] = b"d8:announce3:url4:infod6:lengthi42e4:name1:x6:pieces2:abee";
+
+    #[test]
+    fn test_incremental() {
+        let mut parser = IncrementalParser::new();
+
+        for (i, b) in TORRENT.iter().enumerate() {
+            let expected = if i + 1 == TORRENT.len() { Progress::Complete(TORRENT.len()) } else { Progress::NeedMore };
+            assert_eq!(parser.feed(&[*b]).unwrap(), expected);
+        }
+        assert_eq!(parser.consumed(), 0);
+
+        for input in [&b"i-42e"[..], b"0:", b"le", b"de", b"3:abc", b"ld0:lee0:i0ee"] {
+            assert_eq!(IncrementalParser::new().feed(input).unwrap(), Progress::Complete(input.len()));
+        }
+    }
+
+    #[test]
+    fn test_incremental_framing() {
+        let stream = [TORRENT, b"i7e", b"0:", b"l1:ae"].concat();
+        let mut parser = IncrementalParser::new();
+        let mut buf = Vec::new();
+        let mut values = Vec::new();
+
+        for chunk in stream.chunks(5) {
+            let mut fed = buf.len();
+            buf.extend_from_slice(chunk);
+            while let Progress::Complete(n) = parser.feed(&buf[fed..]).unwrap() {
+                values.push(from_bytes::<Value>(&buf[..n]).unwrap());
+                buf.drain(..n);
+                fed = 0;
+            }
+        }
+
+        assert!(buf.is_empty());
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[1], Value::Integer(7));
+        assert_eq!(values[3], Value::List(vec![Value::from("a")]));
+    }
+
+    #[test]
+    fn test_incremental_err() {
+        let inputs: [&[u8]; 14] = [
+            b"i-0e", b"i01e", b"i-01e", b"iabc", b"i-e", b"i1x", b"01:a", b"3x",
+            b"x", b"e", b"di1ei2ee", b"d1:ae", b"li1ex", b"d1:a1:bx",
+        ];
+
+        for input in inputs {
+            let expected = from_bytes::<RawBencode>(input).unwrap_err();
+            let mut parser = IncrementalParser::new();
+            let err = input.chunks(1)
+                .map(|b| parser.feed(b))
+                .find_map(|r| r.err())
+                .unwrap();
+
+            // Only the deserializer tracks paths.
+            assert_eq!(err.index(), expected.index());
+            assert_eq!(err.kind(), expected.kind());
+        }
+    }
+
+    #[test]
+    fn test_incremental_limits() {
+        let options = DeserializerOptions::new()
+            .recursion_limit(2)
+            .max_bytes_len(4)
+            .max_list_len(2)
+            .max_dict_len(1)
+            .max_input_len(16);
+
+        let inputs: [&[u8]; 5] = [b"llle", b"5:abcde", b"li1ei2ei3ee", b"d1:ai1e1:bi2ee", b"ll4:abcdel4:abcdee"];
+
+        for input in inputs {
+            let expected = options.from_bytes::<RawBencode>(input).unwrap_err();
+            let err = IncrementalParser::with_options(options).feed(input).unwrap_err();
+
+            assert_eq!(err.index(), expected.index());
+            assert_eq!(err.kind(), expected.kind());
+        }
+    }
+
+    #[test]
+    fn test_incremental_strict() {
+        let options = DeserializerOptions::new().strict(true);
+
+        assert_eq!(IncrementalParser::with_options(options).feed(TORRENT).unwrap(), Progress::Complete(TORRENT.len()));
+
+        let inputs: [&[u8]; 4] = [b"d1:bi1e1:ai2ee", b"d1:ai1e1:ai2ee", b"ld2:abi1e1:ai2eee", b"d1:ad1:y0:1:x0:ee"];
+
+        for input in inputs {
+            let expected = options.from_bytes::<RawBencode>(input).unwrap_err();
+            let mut parser = IncrementalParser::with_options(options);
+            let err = input.chunks(1)
+                .map(|b| parser.feed(b))
+                .find_map(|r| r.err())
+                .unwrap();
+
+            assert_eq!(err.index(), expected.index());
+            assert_eq!(err.kind(), expected.kind());
+        }
+    }
+}
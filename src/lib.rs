@@ -1,8 +1,31 @@
+#[cfg(feature = "bigint")]
+pub mod bigint;
 pub mod bytes;
 mod de;
+mod document;
 mod error;
+mod incremental;
+mod options;
+mod raw;
+pub mod read;
 mod ser;
+mod tokenizer;
+mod value;
+mod value_ref;
 
-pub use de::{from_bytes, Deserializer};
-pub use error::{Error, Result};
-pub use ser::{to_bytes, to_writer, Serializer};
+pub use de::{
+    from_bytes, from_bytes_prefix, from_reader, BoolPolicy, Deserializer, DeserializerOptions,
+    StreamDeserializer, StringPolicy,
+};
+pub use document::{Document, Node, NodeKind};
+pub use error::{Category, Error, ErrorKind, Path, PathSegment, Result, Snippet};
+pub use incremental::{IncrementalParser, Progress};
+pub use options::{from_bytes_with, to_bytes_with, Options};
+pub use raw::{RawBencode, RawBencodeBuf};
+pub use ser::{
+    serialized_size, to_bytes, to_writer, EnumRepr, FloatPolicy, NonePolicy, Serializer,
+    SerializerOptions,
+};
+pub use tokenizer::{Event, Tokenizer};
+pub use value::{from_value, to_value, Value};
+pub use value_ref::ValueRef;
@@ -0,0 +1,270 @@
+use std::io;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::de::{BoolPolicy, Deserializer, DeserializerOptions, StringPolicy};
+use super::error::Result;
+use super::read::{IoRead, SliceRead};
+use super::ser::{EnumRepr, FloatPolicy, NonePolicy, Serializer, SerializerOptions};
+
+/// Settings for both directions, configured once.
+///
+/// Settings that decide how a value is represented, such as the float policy
+/// and the enum representation, apply to serializers and deserializers
+/// alike, so that whatever one writes the other reads back. The others only
+/// concern the side they are named after.
+///
+/// ```
+/// use bencode_rs::{FloatPolicy, Options};
+///
+/// let options = Options::new().float_policy(FloatPolicy::Decimal).strict(true);
+/// let bytes = options.to_bytes(&vec![0.5, 2.25]).unwrap();
+///
+/// assert_eq!(bytes, b"l3:0.54:2.25e");
+/// assert_eq!(options.from_bytes::<Vec<f64>>(&bytes).unwrap(), [0.5, 2.25]);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    ser: SerializerOptions,
+    de: DeserializerOptions,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// See `SerializerOptions::sort_keys`.
+    pub fn sort_keys(mut self, sort_keys: bool) -> Self {
+        self.ser = self.ser.sort_keys(sort_keys);
+        self
+    }
+
+    /// See `SerializerOptions::none_policy`.
+    pub fn none_policy(mut self, policy: NonePolicy) -> Self {
+        self.ser = self.ser.none_policy(policy);
+        self
+    }
+
+    /// See `SerializerOptions::initial_capacity`.
+    pub fn initial_capacity(mut self, capacity: usize) -> Self {
+        self.ser = self.ser.initial_capacity(capacity);
+        self
+    }
+
+    /// How floats are encoded and decoded, see `FloatPolicy`.
+    pub fn float_policy(mut self, policy: FloatPolicy) -> Self {
+        self.ser = self.ser.float_policy(policy);
+        self.de = self.de.float_policy(policy);
+        self
+    }
+
+    /// How enum variants are identified, see `EnumRepr`.
+    pub fn enum_repr(mut self, repr: EnumRepr) -> Self {
+        self.ser = self.ser.enum_repr(repr);
+        self.de = self.de.enum_repr(repr);
+        self
+    }
+
+    /// See `DeserializerOptions::strict`.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.de = self.de.strict(strict);
+        self
+    }
+
+    /// See `DeserializerOptions::bool_policy`.
+    pub fn bool_policy(mut self, policy: BoolPolicy) -> Self {
+        self.de = self.de.bool_policy(policy);
+        self
+    }
+
+    /// See `DeserializerOptions::string_policy`.
+    pub fn string_policy(mut self, policy: StringPolicy) -> Self {
+        self.de = self.de.string_policy(policy);
+        self
+    }
+
+    /// See `DeserializerOptions::recursion_limit`.
+    pub fn recursion_limit(mut self, limit: usize) -> Self {
+        self.de = self.de.recursion_limit(limit);
+        self
+    }
+
+    /// See `DeserializerOptions::max_bytes_len`.
+    pub fn max_bytes_len(mut self, len: usize) -> Self {
+        self.de = self.de.max_bytes_len(len);
+        self
+    }
+
+    /// See `DeserializerOptions::max_list_len`.
+    pub fn max_list_len(mut self, len: usize) -> Self {
+        self.de = self.de.max_list_len(len);
+        self
+    }
+
+    /// See `DeserializerOptions::max_dict_len`.
+    pub fn max_dict_len(mut self, len: usize) -> Self {
+        self.de = self.de.max_dict_len(len);
+        self
+    }
+
+    /// See `DeserializerOptions::max_input_len`.
+    pub fn max_input_len(mut self, len: usize) -> Self {
+        self.de = self.de.max_input_len(len);
+        self
+    }
+
+    pub fn serializer_options(&self) -> SerializerOptions {
+        self.ser
+    }
+
+    pub fn deserializer_options(&self) -> DeserializerOptions {
+        self.de
+    }
+
+    pub fn serializer<W: io::Write>(&self, writer: W) -> Serializer<W> {
+        Serializer::with_options(writer, self.ser)
+    }
+
+    pub fn deserializer<'a>(&self, input: &'a [u8]) -> Deserializer<SliceRead<'a>> {
+        Deserializer::with_options(input, self.de)
+    }
+
+    pub fn reader_deserializer<R: io::Read>(&self, reader: R) -> Deserializer<IoRead<R>> {
+        Deserializer::from_reader_with_options(reader, self.de)
+    }
+
+    pub fn to_bytes<T>(&self, value: &T) -> Result<Vec<u8>>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.ser.to_bytes(value)
+    }
+
+    pub fn to_writer<T, W>(&self, value: &T, writer: &mut W) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+        W: io::Write,
+    {
+        self.ser.to_writer(value, writer)
+    }
+
+    /// See `SerializerOptions::serialized_size`.
+    pub fn serialized_size<T>(&self, value: &T) -> Result<usize>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.ser.serialized_size(value)
+    }
+
+    pub fn from_bytes<'de, T>(&self, bytes: &'de [u8]) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        self.de.from_bytes(bytes)
+    }
+
+    pub fn from_reader<R, T>(&self, reader: R) -> Result<T>
+    where
+        R: io::Read,
+        T: DeserializeOwned,
+    {
+        self.de.from_reader(reader)
+    }
+}
+
+impl From<Options> for SerializerOptions {
+    fn from(options: Options) -> Self {
+        options.ser
+    }
+}
+
+impl From<Options> for DeserializerOptions {
+    fn from(options: Options) -> Self {
+        options.de
+    }
+}
+
+pub fn to_bytes_with<T>(value: &T, options: &Options) -> Result<Vec<u8>>
+where
+    T: ?Sized + Serialize,
+{
+    options.to_bytes(value)
+}
+
+pub fn from_bytes_with<'de, T>(bytes: &'de [u8], options: &Options) -> Result<T>
+where
+    T: Deserialize<'de>,
+{
+    options.from_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{from_bytes, to_bytes, EnumRepr, FloatPolicy, NonePolicy, Value};
+    use super::{from_bytes_with, to_bytes_with, Options};
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Rect { w: u8, h: u8 },
+    }
+
+    #[test]
+    fn test_options() {
+        let options = Options::new()
+            .enum_repr(EnumRepr::Index)
+            .float_policy(FloatPolicy::Scaled(1))
+            .none_policy(NonePolicy::Skip);
+        let shapes = vec![Some(Shape::Point), None, Some(Shape::Circle(1.5)), Some(Shape::Rect { w: 1, h: 2 })];
+        let bytes = to_bytes_with(&shapes, &options).unwrap();
+
+        assert_eq!(bytes, b"li0ed1:1i15eed1:2d1:hi2e1:wi1eeee");
+        assert_eq!(
+            from_bytes_with::<Vec<Shape>>(&bytes, &options).unwrap(),
+            [Shape::Point, Shape::Circle(1.5), Shape::Rect { w: 1, h: 2 }]
+        );
+        assert_eq!(options.from_bytes::<Shape>(b"d4:Rectd1:hi2e1:wi1eee").unwrap(), Shape::Rect { w: 1, h: 2 });
+        assert_eq!(to_bytes(&Shape::Point).unwrap(), b"5:Point");
+        assert_eq!(from_bytes::<Shape>(b"5:Point").unwrap(), Shape::Point);
+    }
+
+    #[test]
+    fn test_options_parts() {
+        #[derive(Serialize)]
+        struct Pair { b: u8, a: u8 }
+
+        let options = Options::new().sort_keys(false).strict(true);
+        let mut out = Vec::new();
+        options.to_writer(&Pair { b: 1, a: 2 }, &mut out).unwrap();
+
+        assert_eq!(out, b"d1:bi1e1:ai2ee");
+        assert!(options.from_reader::<_, Value>(&out[..]).is_err());
+        assert!(options.deserializer_options().from_bytes::<Value>(&out).is_err());
+        assert_eq!(options.serializer_options().to_bytes(&Pair { b: 1, a: 2 }).unwrap(), out);
+        assert_eq!(Options::new().from_bytes::<Value>(&out).unwrap(), from_bytes::<Value>(&out).unwrap());
+    }
+
+    #[test]
+    fn test_options_err() {
+        let index = Options::new().enum_repr(EnumRepr::Index);
+        let limited = Options::new().max_list_len(1);
+
+        let a = from_bytes_with::<Shape>(b"i3e", &index);
+        let b = from_bytes_with::<Shape>(b"d1:\xffi1ee", &index);
+        let c = from_bytes_with::<Shape>(b"i0e", &Options::new());
+        let d = from_bytes_with::<Vec<u8>>(b"li1ei2ee", &limited);
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "invalid value: integer `3`, expected variant index 0 <= i < 3");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "expected enum at index 1");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "expected enum at index 0");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "list exceeds the size limit at index 4");
+    }
+}
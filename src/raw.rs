@@ -0,0 +1,242 @@
+use std::fmt;
+
+use serde::{
+    de::{self, Visitor},
+    ser::{self, Serialize},
+    Deserialize,
+};
+
+use super::de::from_bytes;
+use super::error::Result;
+use super::value::RawBytes;
+
+/// Name of the newtype struct through which raw values are exchanged with the
+/// `Serializer` and `Deserializer`.
+pub(crate) const TOKEN: &str = "$bencode_rs::private::RawBencode";
+
+/// The exact encoded bytes of a single Bencode value, borrowed from the input.
+///
+/// When used as a field of a `#[derive(Deserialize)]` struct, the field
+/// captures the bytes the deserializer consumed for it, untouched. The
+/// `Serializer` writes them back verbatim, so hashing `as_bytes()` gives the
+/// same result as hashing the original document, e.g. for a torrent's
+/// info-hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RawBencode<'de> {
+    bytes: &'de [u8],
+}
+
+/// Owned variant of `RawBencode`, for inputs the value cannot borrow from,
+/// such as readers.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawBencodeBuf {
+    bytes: Vec<u8>,
+}
+
+impl<'de> RawBencode<'de> {
+    /// Wraps bytes already known to hold exactly one well formed value.
+    pub(crate) fn new(bytes: &'de [u8]) -> Self {
+        RawBencode { bytes }
+    }
+
+    pub fn as_bytes(&self) -> &'de [u8] {
+        self.bytes
+    }
+
+    pub fn to_owned_buf(&self) -> RawBencodeBuf {
+        RawBencodeBuf { bytes: self.bytes.to_vec() }
+    }
+
+    /// Deserializes the captured value.
+    pub fn deserialize<T>(&self) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        from_bytes(self.bytes)
+    }
+}
+
+impl RawBencodeBuf {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn as_raw(&self) -> RawBencode<'_> {
+        RawBencode { bytes: &self.bytes }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Deserializes the captured value.
+    pub fn deserialize<'de, T>(&'de self) -> Result<T>
+    where
+        T: Deserialize<'de>,
+    {
+        from_bytes(&self.bytes)
+    }
+}
+
+impl Serialize for RawBencode<'_> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_newtype_struct(TOKEN, &RawBytes(self.bytes))
+    }
+}
+
+impl Serialize for RawBencodeBuf {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        self.as_raw().serialize(serializer)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawBencode<'a> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct RawVisitor;
+
+        impl<'de> Visitor<'de> for RawVisitor {
+            type Value = RawBencode<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a raw bencode value borrowed from the input")
+            }
+
+            fn visit_borrowed_bytes<E: de::Error>(self, bytes: &'de [u8]) -> std::result::Result<Self::Value, E> {
+                Ok(RawBencode { bytes })
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(TOKEN, RawVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawBencodeBuf {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct RawBufVisitor;
+
+        impl<'de> Visitor<'de> for RawBufVisitor {
+            type Value = RawBencodeBuf;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a raw bencode value")
+            }
+
+            fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> std::result::Result<Self::Value, E> {
+                Ok(RawBencodeBuf { bytes: bytes.to_vec() })
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> std::result::Result<Self::Value, E> {
+                Ok(RawBencodeBuf { bytes })
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(TOKEN, RawBufVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{from_bytes, from_reader, from_value, to_bytes, to_value, DeserializerOptions, Value};
+    use super::{RawBencode, RawBencodeBuf};
+
+    const TORRENT: &[u8] = b"d8:announce3:url4:infod6:lengthi42e4:name1:x6:pieces2:abee";
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct Torrent<'a> {
+        announce: &'a str,
+        #[serde(borrow)]
+        info: RawBencode<'a>,
+    }
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct OwnedTorrent {
+        announce: String,
+        info: RawBencodeBuf,
+    }
+
+    #[test]
+    fn test_raw_borrowed() {
+        let t: Torrent = from_bytes(TORRENT).unwrap();
+
+        assert_eq!(t.announce, "url");
+        assert_eq!(t.info.as_bytes(), b"d6:lengthi42e4:name1:x6:pieces2:abe");
+        assert_eq!(t.info.deserialize::<Value>().unwrap().get("length"), Some(&Value::Integer(42)));
+        assert_eq!(to_bytes(&t).unwrap(), TORRENT);
+    }
+
+    #[test]
+    fn test_raw_owned() {
+        let t: OwnedTorrent = from_reader(TORRENT).unwrap();
+
+        assert_eq!(t.info.as_bytes(), b"d6:lengthi42e4:name1:x6:pieces2:abe");
+        assert_eq!(to_bytes(&t).unwrap(), TORRENT);
+    }
+
+    #[test]
+    fn test_raw_verbatim() {
+        let raw: RawBencode = from_bytes(b"d1:bi1e1:ai2ee").unwrap();
+
+        assert_eq!(to_bytes(&vec![raw]).unwrap(), b"ld1:bi1e1:ai2eee");
+    }
+
+    #[test]
+    fn test_raw_value() {
+        let t: OwnedTorrent = from_bytes(TORRENT).unwrap();
+        let v = to_value(&t).unwrap();
+
+        assert_eq!(v.get("info").and_then(|i| i.get("name")), Some(&Value::from("x")));
+        assert_eq!(from_value::<OwnedTorrent>(v).unwrap().info, t.info);
+    }
+
+    #[test]
+    fn test_raw_err() {
+        let a = from_bytes::<'_, RawBencode>(b"d1:ai1e");
+        let b = from_bytes::<'_, RawBencode>(b"li1ei2ea");
+        let c = from_bytes::<'_, RawBencode>(b"di1ei2ee");
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "EOF while parsing at index 7");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "expected `e` at index 7");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "key must be a string at index 1");
+    }
+
+    #[test]
+    fn test_raw_strict() {
+        let strict = DeserializerOptions::new().strict(true);
+        let t: Torrent = strict.from_bytes(TORRENT).unwrap();
+
+        assert_eq!(t.info.as_bytes(), b"d6:lengthi42e4:name1:x6:pieces2:abe");
+    }
+
+    #[test]
+    fn test_raw_strict_err() {
+        let strict = DeserializerOptions::new().strict(true);
+
+        let a = strict.from_bytes::<Torrent>(b"d8:announce3:url4:infod1:bi1e1:ai2eee");
+        let b = strict.from_bytes::<RawBencode>(b"ld1:ai1e1:ai2eee");
+        let c = strict.from_reader::<_, OwnedTorrent>(&b"d8:announce3:url4:infod1:bi1e1:ai2eee"[..]);
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "dictionary keys are not sorted at index 29 in info");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "duplicate dictionary key at index 8 in [0]");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "dictionary keys are not sorted at index 29 in info");
+    }
+}
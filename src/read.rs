@@ -0,0 +1,299 @@
+use std::{io::{self, Read as _}, ops::Deref};
+
+use serde::de::Visitor;
+
+use super::error::{Error, Result};
+
+/// Trait used by the deserializer for iterating over input. This is manually
+/// "specialized" for iterating over `&[u8]`, allowing byte strings to be
+/// borrowed from the input instead of copied.
+///
+/// This trait is sealed and cannot be implemented for types outside of
+/// `bencode_rs`.
+pub trait Read<'de>: private::Sealed {
+    #[doc(hidden)]
+    fn next(&mut self) -> Result<Option<u8>>;
+
+    #[doc(hidden)]
+    fn peek(&mut self) -> Result<Option<u8>>;
+
+    /// Number of bytes consumed so far.
+    #[doc(hidden)]
+    fn index(&self) -> usize;
+
+    /// Reads the next `len` bytes, borrowing them from the input when
+    /// possible and copying them into `scratch` otherwise.
+    #[doc(hidden)]
+    fn parse_bytes<'s>(
+        &'s mut self,
+        len: usize,
+        scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'de, 's, [u8]>>;
+
+    /// Consumes the next `len` bytes without handing them out.
+    #[doc(hidden)]
+    fn skip_bytes(&mut self, len: usize) -> Result<()>;
+
+    /// Starts recording the bytes consumed from now on.
+    #[doc(hidden)]
+    fn begin_raw_buffering(&mut self);
+
+    /// Hands the bytes consumed since `begin_raw_buffering` to the visitor,
+    /// borrowed from the input when possible.
+    #[doc(hidden)]
+    fn end_raw_buffering<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>;
+}
+
+pub enum Reference<'b, 'c, T>
+where
+    T: ?Sized + 'static,
+{
+    Borrowed(&'b T),
+    Copied(&'c T),
+}
+
+impl<T> Deref for Reference<'_, '_, T>
+where
+    T: ?Sized + 'static,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        match *self {
+            Reference::Borrowed(b) => b,
+            Reference::Copied(c) => c,
+        }
+    }
+}
+
+/// Bencode input source that reads from a slice of bytes.
+pub struct SliceRead<'a> {
+    slice: &'a [u8],
+    index: usize,
+    raw_buffering_start_index: usize,
+}
+
+impl<'a> SliceRead<'a> {
+    pub fn new(slice: &'a [u8]) -> Self {
+        SliceRead { slice, index: 0, raw_buffering_start_index: 0 }
+    }
+}
+
+impl<'a> Read<'a> for SliceRead<'a> {
+    #[inline]
+    fn next(&mut self) -> Result<Option<u8>> {
+        Ok(if self.index < self.slice.len() {
+            let b = self.slice[self.index];
+            self.index += 1;
+            Some(b)
+        } else {
+            None
+        })
+    }
+
+    #[inline]
+    fn peek(&mut self) -> Result<Option<u8>> {
+        Ok(self.slice.get(self.index).copied())
+    }
+
+    #[inline]
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    fn parse_bytes<'s>(
+        &'s mut self,
+        len: usize,
+        _scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'a, 's, [u8]>> {
+        let s = self.index
+            .checked_add(len)
+            .and_then(|end| self.slice.get(self.index..end))
+            .ok_or_else(|| Error::eof(self.slice.len()))?;
+        self.index += len;
+
+        Ok(Reference::Borrowed(s))
+    }
+
+    fn skip_bytes(&mut self, len: usize) -> Result<()> {
+        match self.index.checked_add(len) {
+            Some(end) if end <= self.slice.len() => {
+                self.index = end;
+                Ok(())
+            },
+            _ => Err(Error::eof(self.slice.len())),
+        }
+    }
+
+    fn begin_raw_buffering(&mut self) {
+        self.raw_buffering_start_index = self.index;
+    }
+
+    fn end_raw_buffering<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_borrowed_bytes(&self.slice[self.raw_buffering_start_index..self.index])
+    }
+}
+
+/// Bencode input source that reads from a `std::io` input stream.
+///
+/// Single bytes are read one at a time, so wrapping unbuffered sources such
+/// as files or sockets in an `io::BufReader` is strongly recommended.
+pub struct IoRead<R>
+where
+    R: io::Read,
+{
+    reader: R,
+    peeked: Option<u8>,
+    index: usize,
+    raw_buffer: Option<Vec<u8>>,
+}
+
+impl<R> IoRead<R>
+where
+    R: io::Read,
+{
+    pub fn new(reader: R) -> Self {
+        IoRead { reader, peeked: None, index: 0, raw_buffer: None }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns the underlying reader. A byte the deserializer peeked at
+    /// without consuming, such as the one that ended the last value with
+    /// `TrailingCharacters`, is lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>> {
+        let mut buf = [0u8];
+        loop {
+            match self.reader.read(&mut buf) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(buf[0])),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(Error::io(e)),
+            }
+        }
+    }
+}
+
+impl<'de, R> Read<'de> for IoRead<R>
+where
+    R: io::Read,
+{
+    #[inline]
+    fn next(&mut self) -> Result<Option<u8>> {
+        let b = match self.peeked.take() {
+            Some(b) => Some(b),
+            None => self.read_byte()?,
+        };
+        if let Some(b) = b {
+            self.index += 1;
+            if let Some(ref mut buf) = self.raw_buffer {
+                buf.push(b);
+            }
+        }
+        Ok(b)
+    }
+
+    #[inline]
+    fn peek(&mut self) -> Result<Option<u8>> {
+        if self.peeked.is_none() {
+            self.peeked = self.read_byte()?;
+        }
+        Ok(self.peeked)
+    }
+
+    #[inline]
+    fn index(&self) -> usize {
+        self.index
+    }
+
+    fn parse_bytes<'s>(
+        &'s mut self,
+        len: usize,
+        scratch: &'s mut Vec<u8>,
+    ) -> Result<Reference<'de, 's, [u8]>> {
+        scratch.clear();
+        if len > 0 {
+            if let Some(b) = self.peeked.take() {
+                scratch.push(b);
+            }
+        }
+
+        // `take` guards against allocating the declared length up front, the
+        // buffer only grows as fast as data actually arrives.
+        let remaining = (len - scratch.len()) as u64;
+        io::Read::take(&mut self.reader, remaining)
+            .read_to_end(scratch)
+            .map_err(Error::io)?;
+        self.index += scratch.len();
+        if let Some(ref mut buf) = self.raw_buffer {
+            buf.extend_from_slice(scratch);
+        }
+
+        if scratch.len() < len {
+            return Err(Error::eof(self.index));
+        }
+
+        Ok(Reference::Copied(scratch))
+    }
+
+    fn skip_bytes(&mut self, len: usize) -> Result<()> {
+        let mut skipped = 0;
+        if len > 0 {
+            if let Some(b) = self.peeked.take() {
+                if let Some(ref mut buf) = self.raw_buffer {
+                    buf.push(b);
+                }
+                skipped = 1;
+            }
+        }
+
+        let mut reader = io::Read::take(&mut self.reader, (len - skipped) as u64);
+        let copied = match self.raw_buffer {
+            Some(ref mut buf) => io::copy(&mut reader, buf),
+            None => io::copy(&mut reader, &mut io::sink()),
+        };
+        skipped += copied.map_err(Error::io)? as usize;
+        self.index += skipped;
+
+        if skipped < len {
+            return Err(Error::eof(self.index));
+        }
+
+        Ok(())
+    }
+
+    fn begin_raw_buffering(&mut self) {
+        self.raw_buffer = Some(Vec::new());
+    }
+
+    fn end_raw_buffering<V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let raw = self.raw_buffer.take().unwrap_or_default();
+        visitor.visit_byte_buf(raw)
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+impl private::Sealed for SliceRead<'_> {}
+
+impl<R> private::Sealed for IoRead<R> where R: io::Read {}
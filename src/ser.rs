@@ -1,20 +1,177 @@
-use std::io;
+use std::{fmt, io};
 use serde::{ser, Serialize};
 
-use super::error::{Error, Result};
+#[cfg(feature = "bigint")]
+use super::bigint;
+use super::error::{Error, Result, ErrorKind, PathSegment};
+use super::raw;
 
 pub struct Serializer<W: io::Write> {
     writer: W,
+    options: SerializerOptions,
+}
+
+/// Settings controlling how values are encoded.
+#[derive(Debug, Clone, Copy)]
+pub struct SerializerOptions {
+    sort_keys: bool,
+    none_policy: NonePolicy,
+    float_policy: FloatPolicy,
+    enum_repr: EnumRepr,
+    initial_capacity: usize,
+}
+
+/// Bencode has no null. `None` and unit dictionary values are left out along
+/// with their key; this decides what happens to them inside lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonePolicy {
+    /// Serialization fails.
+    Reject,
+    /// The element is left out, shortening the list.
+    Skip,
+}
+
+/// Bencode has no floating point type. This decides how `f32` and `f64`
+/// values are encoded, and which encoding the `Deserializer` reads them from,
+/// so that the same policy on both sides round-trips them. Policies writing
+/// integers reject NaN, infinities and results outside the `i64` range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatPolicy {
+    /// Serialization fails. Floats are still read from integers.
+    Reject,
+    /// The fractional part is dropped and the integer is written.
+    Truncate,
+    /// The nearest integer is written, rounding half-way cases away from
+    /// zero.
+    Round,
+    /// A byte string holding the shortest decimal representation that reads
+    /// back to the same value, e.g. `4:0.25`. Integers are accepted too when
+    /// deserializing.
+    Decimal,
+    /// The value multiplied by ten to the given power and rounded, e.g.
+    /// `0.25` is written as `i2500e` with a scale of 4. Scales above
+    /// `f64::MAX_10_EXP` are rejected.
+    Scaled(u32),
+}
+
+/// How enum variants are identified, both when serializing and when
+/// deserializing. Unit variants are written as the identifier alone, other
+/// variants as a dictionary with the identifier as its only key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnumRepr {
+    /// By name, e.g. `4:Unit` or `d4:Sizei3ee`.
+    Name,
+    /// By index in declaration order, e.g. `i0e` or `d1:1i3ee`. More compact,
+    /// but reordering the variants changes the encoding. Names are still
+    /// accepted when deserializing.
+    Index,
+}
+
+impl Default for SerializerOptions {
+    fn default() -> Self {
+        SerializerOptions {
+            sort_keys: true,
+            none_policy: NonePolicy::Reject,
+            float_policy: FloatPolicy::Reject,
+            enum_repr: EnumRepr::Name,
+            initial_capacity: 128,
+        }
+    }
+}
+
+impl SerializerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether dictionary keys are sorted as raw byte strings, as BEP 3
+    /// requires. Enabled by default; when disabled, entries are written in
+    /// the order serde yields them.
+    pub fn sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// How `None` and unit elements of lists are handled. Defaults to
+    /// `NonePolicy::Reject`.
+    pub fn none_policy(mut self, policy: NonePolicy) -> Self {
+        self.none_policy = policy;
+        self
+    }
+
+    /// How floats are encoded. Defaults to `FloatPolicy::Reject`, so that no
+    /// precision is lost unless a lossy policy is chosen.
+    pub fn float_policy(mut self, policy: FloatPolicy) -> Self {
+        self.float_policy = policy;
+        self
+    }
+
+    /// How enum variants are identified. Defaults to `EnumRepr::Name`.
+    pub fn enum_repr(mut self, repr: EnumRepr) -> Self {
+        self.enum_repr = repr;
+        self
+    }
+
+    /// Number of bytes `to_bytes` reserves up front. Defaults to 128.
+    pub fn initial_capacity(mut self, capacity: usize) -> Self {
+        self.initial_capacity = capacity;
+        self
+    }
+
+    pub fn to_bytes<T>(&self, value: &T) -> Result<Vec<u8>>
+    where
+        T: ?Sized + Serialize,
+    {
+        let vec = Vec::with_capacity(self.initial_capacity);
+        let mut serializer = Serializer::with_options(vec, *self);
+        value.serialize(&mut serializer)?;
+        Ok(serializer.writer)
+    }
+
+    pub fn to_writer<T, W>(&self, value: &T, writer: &mut W) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+        W: io::Write,
+    {
+        let mut serializer = Serializer::with_options(writer, *self);
+        value.serialize(&mut serializer)
+    }
+
+    /// Number of bytes `to_bytes` would produce, computed without building
+    /// the output.
+    pub fn serialized_size<T>(&self, value: &T) -> Result<usize>
+    where
+        T: ?Sized + Serialize,
+    {
+        // Sorting the keys does not change the length, and writing entries
+        // in their original order avoids buffering them.
+        let mut serializer = Serializer::with_options(Counter { len: 0 }, self.sort_keys(false));
+        value.serialize(&mut serializer)?;
+        Ok(serializer.writer.len)
+    }
+}
+
+/// Writer that only counts the bytes written to it.
+struct Counter {
+    len: usize,
+}
+
+impl io::Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.len += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 pub fn to_bytes<T>(value: &T) -> Result<Vec<u8>>
 where
     T: Serialize
 {
-    let vec = Vec::with_capacity(128);
-    let mut serializer = Serializer::new(vec);
-    value.serialize(&mut serializer)?;
-    Ok(serializer.writer)
+    SerializerOptions::default().to_bytes(value)
 }
 
 pub fn to_writer<T, W>(value: &T, writer: &mut W) -> Result<()>
@@ -22,27 +179,103 @@ where
     T: Serialize,
     W: io::Write,
 {
-    let mut serializer = Serializer::new(writer);
-    value.serialize(&mut serializer)
+    SerializerOptions::default().to_writer(value, writer)
+}
+
+/// Number of bytes `to_bytes` would produce for `value`, e.g. to check that a
+/// message fits in a packet before encoding it.
+pub fn serialized_size<T>(value: &T) -> Result<usize>
+where
+    T: ?Sized + Serialize,
+{
+    SerializerOptions::default().serialized_size(value)
 }
 
 impl<W: io::Write> Serializer<W> {
-    fn new(writer: W) -> Self {
-        Serializer { writer }
+    pub fn new(writer: W) -> Self {
+        Serializer::with_options(writer, SerializerOptions::default())
+    }
+
+    pub fn with_options(writer: W, options: SerializerOptions) -> Self {
+        Serializer { writer, options }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Writing to the writer between two values is fine, writing while a
+    /// value is being serialized corrupts it.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_bytes(&mut self, v: &[u8]) -> Result<()> {
+        self.writer.write_all(itoa::Buffer::new().format(v.len()).as_bytes())?;
+        self.writer.write_all(b":")?;
+        self.writer.write_all(v)
+            .map_err(Into::into)
+    }
+
+    /// Writes the identifier of an enum variant according to the enum
+    /// representation.
+    fn write_variant(&mut self, variant_index: u32, variant: &str) -> Result<()> {
+        match self.options.enum_repr {
+            EnumRepr::Name => self.write_bytes(variant.as_bytes()),
+            EnumRepr::Index => self.write_bytes(itoa::Buffer::new().format(variant_index).as_bytes()),
+        }
+    }
+
+    /// Writes a float as an integer according to the float policy.
+    fn serialize_float(&mut self, v: f64) -> Result<()> {
+        let int = match self.options.float_policy {
+            FloatPolicy::Truncate => v.trunc(),
+            FloatPolicy::Round => v.round(),
+            FloatPolicy::Scaled(scale) => {
+                let factor = scale_factor(scale).ok_or_else(|| Error::data(ErrorKind::FloatNotRepresentable))?;
+                (v * factor).round()
+            },
+            FloatPolicy::Reject | FloatPolicy::Decimal => return Err(Error::data(ErrorKind::FloatNotRepresentable)),
+        };
+
+        // `i64::MAX` is not representable as `f64`, the bound is its
+        // successor.
+        if !(int >= i64::MIN as f64 && int < i64::MAX as f64) {
+            return Err(Error::data(ErrorKind::FloatNotRepresentable));
+        }
+        ser::Serializer::serialize_i64(self, int as i64)
+    }
+
+    fn serialize_dict(&mut self, end: &'static [u8]) -> Result<SerializeDict<'_, W>> {
+        self.writer.write_all(b"d")?;
+        let entries = if self.options.sort_keys { Some(Vec::new()) } else { None };
+        Ok(SerializeDict { ser: self, entries, key: None, end })
     }
 }
 
+/// Ten to the power of `scale`, if `f64` can hold it.
+pub(crate) fn scale_factor(scale: u32) -> Option<f64> {
+    if scale > f64::MAX_10_EXP as u32 {
+        return None;
+    }
+    Some(10f64.powi(scale as i32))
+}
+
 impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
     type Ok = ();
     type Error = Error;
 
-    type SerializeSeq = Self;
-    type SerializeTuple = Self;
-    type SerializeTupleStruct = Self;
-    type SerializeTupleVariant = Self;
-    type SerializeMap = Self;
-    type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
+    type SerializeSeq = SerializeList<'a, W>;
+    type SerializeTuple = SerializeList<'a, W>;
+    type SerializeTupleStruct = SerializeList<'a, W>;
+    type SerializeTupleVariant = SerializeList<'a, W>;
+    type SerializeMap = SerializeDict<'a, W>;
+    type SerializeStruct = SerializeDict<'a, W>;
+    type SerializeStructVariant = SerializeDict<'a, W>;
 
     fn serialize_bool(self, v: bool) -> Result<()> {
         if v { self.serialize_i64(1) }
@@ -87,26 +320,32 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
             .map_err(Into::into)
     }
 
-    // Bencode format does not support floats
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.writer.write_all(b"i")?;
+        self.writer.write_all(itoa::Buffer::new().format(v).as_bytes())?;
+        self.writer.write_all(b"e")
+            .map_err(Into::into)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.writer.write_all(b"i")?;
+        self.writer.write_all(itoa::Buffer::new().format(v).as_bytes())?;
+        self.writer.write_all(b"e")
+            .map_err(Into::into)
+    }
+
     fn serialize_f32(self, v: f32) -> Result<()> {
-        log::warn!(
-            "WARNING: Possible data corruption detected with value \"{}\": \n\
-            Bencoding only defines support for integers, value was converted to {}",
-            v,
-            v.trunc(),
-        );
-        self.serialize_i64(v.trunc() as i64)
+        match self.options.float_policy {
+            FloatPolicy::Decimal => self.serialize_str(&v.to_string()),
+            _ => self.serialize_float(f64::from(v)),
+        }
     }
 
-    // Bencode format does not support floats
     fn serialize_f64(self, v: f64) -> Result<()> {
-        log::warn!(
-            "WARNING: Possible data corruption detected with value \"{}\": \n\
-            Bencoding only defines support for integers, value was converted to {}",
-            v,
-            v.trunc(),
-        );
-        self.serialize_i64(v.trunc() as i64)
+        match self.options.float_policy {
+            FloatPolicy::Decimal => self.serialize_str(&v.to_string()),
+            _ => self.serialize_float(v),
+        }
     }
 
     fn serialize_char(self, v: char) -> Result<()> {
@@ -114,17 +353,11 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
-        self.writer.write_all(itoa::Buffer::new().format(v.len()).as_bytes())?;
-        self.writer.write_all(b":")?;
-        self.writer.write_all(v.as_bytes())
-            .map_err(Into::into)
+        self.write_bytes(v.as_bytes())
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        self.writer.write_all(itoa::Buffer::new().format(v.len()).as_bytes())?;
-        self.writer.write_all(b":")?;
-        self.writer.write_all(v)
-            .map_err(Into::into)
+        self.write_bytes(v)
     }
 
     fn serialize_none(self) -> Result<()> {
@@ -149,35 +382,60 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
     fn serialize_unit_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
     ) -> Result<()> {
-        self.serialize_str(variant)
+        match self.options.enum_repr {
+            EnumRepr::Name => self.serialize_str(variant),
+            EnumRepr::Index => self.serialize_u32(variant_index),
+        }
     }
 
     fn serialize_newtype_struct<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &T,
     ) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        if name == raw::TOKEN {
+            let raw = value.serialize(KeySerializer)?;
+            return self.writer.write_all(&raw)
+                .map_err(Into::into);
+        }
+        #[cfg(feature = "bigint")]
+        if name == bigint::TOKEN {
+            let digits = value.serialize(KeySerializer)?;
+            self.writer.write_all(b"i")?;
+            self.writer.write_all(&digits)?;
+            return self.writer.write_all(b"e")
+                .map_err(Into::into);
+        }
+
         value.serialize(self)
     }
 
     fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         value: &T,
     ) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        // The variant would be a key without a value.
+        if is_unit(value) {
+            return Err(
+                <Error as ser::Error>::custom("unit and `None` cannot be represented as a variant value")
+                    .nested(PathSegment::Key(variant.as_bytes().to_vec()), None)
+            );
+        }
+
         self.writer.write_all(b"d")?;
-        variant.serialize(&mut *self)?;
+        self.write_variant(variant_index, variant)?;
         value.serialize(&mut *self)?;
         self.writer.write_all(b"e")
             .map_err(Into::into)
@@ -185,7 +443,7 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
         self.writer.write_all(b"l")?;
-        Ok(self)
+        Ok(SerializeList { ser: self, len: 0, end: b"e" })
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
@@ -203,19 +461,18 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
         self.writer.write_all(b"d")?;
-        variant.serialize(&mut *self)?;
+        self.write_variant(variant_index, variant)?;
         self.writer.write_all(b"l")?;
-        Ok(self)
+        Ok(SerializeList { ser: self, len: 0, end: b"ee" })
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.writer.write_all(b"d")?;
-        Ok(self)
+        self.serialize_dict(b"e")
     }
 
     fn serialize_struct(
@@ -229,18 +486,57 @@ impl<'a, W: io::Write> ser::Serializer for &'a mut Serializer<W> {
     fn serialize_struct_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
         self.writer.write_all(b"d")?;
-        variant.serialize(&mut *self)?;
-        self.writer.write_all(b"d")?;
-        Ok(self)
+        self.write_variant(variant_index, variant)?;
+        self.serialize_dict(b"ee")
+    }
+}
+
+/// List under construction, counting its elements to report them in the
+/// path of errors.
+pub struct SerializeList<'a, W: io::Write> {
+    ser: &'a mut Serializer<W>,
+    len: usize,
+    end: &'static [u8],
+}
+
+impl<W: io::Write> SerializeList<'_, W> {
+    fn serialize_list_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        if is_unit(value) {
+            return match self.ser.options.none_policy {
+                NonePolicy::Reject => Err(
+                    <Error as ser::Error>::custom("unit and `None` cannot be represented inside a list")
+                        .nested(PathSegment::Index(self.len), None)
+                ),
+                // Paths refer to the elements as given, skipped ones
+                // included.
+                NonePolicy::Skip => {
+                    self.len += 1;
+                    Ok(())
+                }
+            };
+        }
+
+        value.serialize(&mut *self.ser)
+            .map_err(|e| e.nested(PathSegment::Index(self.len), None))?;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn end_list(self) -> Result<()> {
+        self.ser.writer.write_all(self.end)
+            .map_err(Into::into)
     }
 }
 
-impl<'a, W: io::Write> ser::SerializeSeq for &'a mut Serializer<W> {
+impl<W: io::Write> ser::SerializeSeq for SerializeList<'_, W> {
     type Ok = ();
     type Error = Error;
 
@@ -248,16 +544,15 @@ impl<'a, W: io::Write> ser::SerializeSeq for &'a mut Serializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.serialize_list_element(value)
     }
 
     fn end(self) -> Result<()> {
-        self.writer.write_all(b"e")
-            .map_err(Into::into)
+        self.end_list()
     }
 }
 
-impl<'a, W: io::Write> ser::SerializeTuple for &'a mut Serializer<W> {
+impl<W: io::Write> ser::SerializeTuple for SerializeList<'_, W> {
     type Ok = ();
     type Error = Error;
 
@@ -265,16 +560,15 @@ impl<'a, W: io::Write> ser::SerializeTuple for &'a mut Serializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.serialize_list_element(value)
     }
 
     fn end(self) -> Result<()> {
-        self.writer.write_all(b"e")
-            .map_err(Into::into)
+        self.end_list()
     }
 }
 
-impl<'a, W: io::Write> ser::SerializeTupleStruct for &'a mut Serializer<W> {
+impl<W: io::Write> ser::SerializeTupleStruct for SerializeList<'_, W> {
     type Ok = ();
     type Error = Error;
 
@@ -282,16 +576,15 @@ impl<'a, W: io::Write> ser::SerializeTupleStruct for &'a mut Serializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.serialize_list_element(value)
     }
 
     fn end(self) -> Result<()> {
-        self.writer.write_all(b"e")
-            .map_err(Into::into)
+        self.end_list()
     }
 }
 
-impl<'a, W: io::Write> ser::SerializeTupleVariant for &'a mut Serializer<W> {
+impl<W: io::Write> ser::SerializeTupleVariant for SerializeList<'_, W> {
     type Ok = ();
     type Error = Error;
 
@@ -299,16 +592,69 @@ impl<'a, W: io::Write> ser::SerializeTupleVariant for &'a mut Serializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        self.serialize_list_element(value)
     }
 
     fn end(self) -> Result<()> {
-        self.writer.write_all(b"ee")
+        self.end_list()
+    }
+}
+
+/// Dictionary under construction. When keys are sorted, every entry is
+/// encoded into its own buffer and the whole dictionary is written out once
+/// all entries are known.
+pub struct SerializeDict<'a, W: io::Write> {
+    ser: &'a mut Serializer<W>,
+    entries: Option<Vec<(Vec<u8>, Vec<u8>)>>,
+    key: Option<Vec<u8>>,
+    end: &'static [u8],
+}
+
+impl<W: io::Write> SerializeDict<'_, W> {
+    fn serialize_entry_value<T>(&mut self, key: Vec<u8>, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        // A key without a value would make the dictionary invalid.
+        if is_unit(value) {
+            return Ok(());
+        }
+
+        match self.entries {
+            Some(ref mut entries) => {
+                let mut ser = Serializer::with_options(Vec::new(), self.ser.options);
+                value.serialize(&mut ser)
+                    .map_err(|e| e.nested(PathSegment::Key(key.clone()), None))?;
+                entries.push((key, ser.writer));
+                Ok(())
+            },
+            None => {
+                self.ser.write_bytes(&key)?;
+                value.serialize(&mut *self.ser)
+                    .map_err(|e| e.nested(PathSegment::Key(key), None))
+            },
+        }
+    }
+
+    fn end_dict(self) -> Result<()> {
+        if let Some(mut entries) = self.entries {
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            // Possible with `#[serde(flatten)]` or hand-written impls.
+            if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+                let key = pair[0].0.clone();
+                return Err(Error::data(ErrorKind::DuplicateKey).nested(PathSegment::Key(key), None));
+            }
+            for (key, value) in entries {
+                self.ser.write_bytes(&key)?;
+                self.ser.writer.write_all(&value)?;
+            }
+        }
+        self.ser.writer.write_all(self.end)
             .map_err(Into::into)
     }
 }
 
-impl<'a, W: io::Write> ser::SerializeMap for &'a mut Serializer<W> {
+impl<W: io::Write> ser::SerializeMap for SerializeDict<'_, W> {
     type Ok = ();
     type Error = Error;
 
@@ -316,23 +662,25 @@ impl<'a, W: io::Write> ser::SerializeMap for &'a mut Serializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
     }
 
     fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        let key = self.key.take()
+            .ok_or_else(|| <Error as ser::Error>::custom("serialize_value called before serialize_key"))?;
+        self.serialize_entry_value(key, value)
     }
 
     fn end(self) -> Result<()> {
-        self.writer.write_all(b"e")
-            .map_err(Into::into)
+        self.end_dict()
     }
 }
 
-impl<'a, W: io::Write> ser::SerializeStruct for &'a mut Serializer<W> {
+impl<W: io::Write> ser::SerializeStruct for SerializeDict<'_, W> {
     type Ok = ();
     type Error = Error;
 
@@ -340,17 +688,15 @@ impl<'a, W: io::Write> ser::SerializeStruct for &'a mut Serializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)?;
-        value.serialize(&mut **self)
+        self.serialize_entry_value(key.as_bytes().to_vec(), value)
     }
 
     fn end(self) -> Result<()> {
-        self.writer.write_all(b"e")
-            .map_err(Into::into)
+        self.end_dict()
     }
 }
 
-impl<'a, W: io::Write> ser::SerializeStructVariant for &'a mut Serializer<W> {
+impl<W: io::Write> ser::SerializeStructVariant for SerializeDict<'_, W> {
     type Ok = ();
     type Error = Error;
 
@@ -358,21 +704,415 @@ impl<'a, W: io::Write> ser::SerializeStructVariant for &'a mut Serializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut **self)?;
-        value.serialize(&mut **self)
+        self.serialize_entry_value(key.as_bytes().to_vec(), value)
     }
 
     fn end(self) -> Result<()> {
-        self.writer.write_all(b"ee")
-            .map_err(Into::into)
+        self.end_dict()
+    }
+}
+
+/// Extracts the raw bytes of a string. Used for dictionary keys, and for
+/// `RawBencode` values which are written out verbatim.
+struct KeySerializer;
+
+#[cold]
+fn key_must_be_a_string() -> Error {
+    Error::data(ErrorKind::KeyMustBeAString)
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Vec<u8>;
+    type Error = Error;
+
+    type SerializeSeq = ser::Impossible<Vec<u8>, Error>;
+    type SerializeTuple = ser::Impossible<Vec<u8>, Error>;
+    type SerializeTupleStruct = ser::Impossible<Vec<u8>, Error>;
+    type SerializeTupleVariant = ser::Impossible<Vec<u8>, Error>;
+    type SerializeMap = ser::Impossible<Vec<u8>, Error>;
+    type SerializeStruct = ser::Impossible<Vec<u8>, Error>;
+    type SerializeStructVariant = ser::Impossible<Vec<u8>, Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_i128(self, _v: i128) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_u128(self, _v: u128) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Vec<u8>> {
+        Ok(v.to_string().into_bytes())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Vec<u8>> {
+        Ok(v.as_bytes().to_vec())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Vec<u8>> {
+        Ok(v.to_vec())
+    }
+
+    fn serialize_none(self) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Vec<u8>>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Vec<u8>> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Vec<u8>> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Vec<u8>>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Vec<u8>>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(key_must_be_a_string())
+    }
+}
+
+/// Whether `value` serializes to nothing, i.e. is `None` or unit.
+fn is_unit<T>(value: &T) -> bool
+where
+    T: ?Sized + Serialize,
+{
+    value.serialize(UnitProbe).is_ok()
+}
+
+/// Succeeds only for values that produce no output, stopping at the first
+/// call for anything else so that nothing is actually serialized.
+struct UnitProbe;
+
+#[derive(Debug)]
+struct NotUnit;
+
+impl fmt::Display for NotUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("not a unit value")
+    }
+}
+
+impl std::error::Error for NotUnit {}
+
+impl ser::Error for NotUnit {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        NotUnit
+    }
+}
+
+impl ser::Serializer for UnitProbe {
+    type Ok = ();
+    type Error = NotUnit;
+
+    type SerializeSeq = ser::Impossible<(), NotUnit>;
+    type SerializeTuple = ser::Impossible<(), NotUnit>;
+    type SerializeTupleStruct = ser::Impossible<(), NotUnit>;
+    type SerializeTupleVariant = ser::Impossible<(), NotUnit>;
+    type SerializeMap = ser::Impossible<(), NotUnit>;
+    type SerializeStruct = ser::Impossible<(), NotUnit>;
+    type SerializeStructVariant = ser::Impossible<(), NotUnit>;
+
+    fn serialize_bool(self, _v: bool) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_i8(self, _v: i8) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_i16(self, _v: i16) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_i32(self, _v: i32) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_i64(self, _v: i64) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_u8(self, _v: u8) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_u16(self, _v: u16) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_u32(self, _v: u32) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_u64(self, _v: u64) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_i128(self, _v: i128) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_u128(self, _v: u128) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_f32(self, _v: f32) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_f64(self, _v: f64) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_char(self, _v: char) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_str(self, _v: &str) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_none(self) -> std::result::Result<(), NotUnit> {
+        Ok(())
+    }
+
+    fn serialize_some<T>(self, value: &T) -> std::result::Result<(), NotUnit>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> std::result::Result<(), NotUnit> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> std::result::Result<(), NotUnit> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> std::result::Result<(), NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> std::result::Result<(), NotUnit>
+    where
+        T: ?Sized + Serialize,
+    {
+        // Raw values are written verbatim even when empty.
+        if name == raw::TOKEN {
+            return Err(NotUnit);
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> std::result::Result<(), NotUnit>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotUnit)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> std::result::Result<Self::SerializeSeq, NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> std::result::Result<Self::SerializeTuple, NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> std::result::Result<Self::SerializeTupleStruct, NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> std::result::Result<Self::SerializeTupleVariant, NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> std::result::Result<Self::SerializeMap, NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> std::result::Result<Self::SerializeStruct, NotUnit> {
+        Err(NotUnit)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> std::result::Result<Self::SerializeStructVariant, NotUnit> {
+        Err(NotUnit)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
+    use std::collections::{BTreeMap, HashMap};
     use serde::Serialize;
-    use super::to_bytes;
+    use super::{serialized_size, to_bytes, FloatPolicy, NonePolicy, Serializer, SerializerOptions};
 
     #[test]
     fn test_int() {
@@ -388,11 +1128,64 @@ mod tests {
         assert_eq!(to_bytes(&i).unwrap(), b"i42e");
     }
 
+    #[test]
+    fn test_int_range() {
+        assert_eq!(to_bytes(&i64::MIN).unwrap(), b"i-9223372036854775808e");
+        assert_eq!(to_bytes(&u64::MAX).unwrap(), b"i18446744073709551615e");
+        assert_eq!(to_bytes(&i128::MIN).unwrap(), b"i-170141183460469231731687303715884105728e");
+        assert_eq!(to_bytes(&u128::MAX).unwrap(), b"i340282366920938463463374607431768211455e");
+    }
+
     #[test]
     fn test_float() {
         let f = 42.69f32;
+        let truncate = SerializerOptions::new().float_policy(FloatPolicy::Truncate);
 
-        assert_eq!(to_bytes(&f).unwrap(), b"i42e");
+        assert_eq!(truncate.to_bytes(&f).unwrap(), b"i42e");
+    }
+
+    #[test]
+    fn test_float_policy() {
+        let round = SerializerOptions::new().float_policy(FloatPolicy::Round);
+        let decimal = SerializerOptions::new().float_policy(FloatPolicy::Decimal);
+        let scaled = SerializerOptions::new().float_policy(FloatPolicy::Scaled(3));
+
+        assert_eq!(round.to_bytes(&42.69f32).unwrap(), b"i43e");
+        assert_eq!(round.to_bytes(&-2.5f64).unwrap(), b"i-3e");
+        assert_eq!(decimal.to_bytes(&0.1f32).unwrap(), b"3:0.1");
+        assert_eq!(decimal.to_bytes(&-1e-7f64).unwrap(), b"10:-0.0000001");
+        assert_eq!(decimal.to_bytes(&f64::INFINITY).unwrap(), b"3:inf");
+        assert_eq!(scaled.to_bytes(&vec![1.2345f64, -0.5]).unwrap(), b"li1235ei-500ee");
+    }
+
+    #[test]
+    fn test_float_policy_err() {
+        let a = SerializerOptions::new().float_policy(FloatPolicy::Reject).to_bytes(&BTreeMap::from([("a", 1.0)]));
+        let b = SerializerOptions::new().float_policy(FloatPolicy::Round).to_bytes(&f64::NAN);
+        let c = SerializerOptions::new().float_policy(FloatPolicy::Scaled(18)).to_bytes(&10.0);
+        let d = SerializerOptions::new().float_policy(FloatPolicy::Scaled(u32::MAX)).to_bytes(&0.5);
+        let e = to_bytes(&42.69f32);
+        let truncate = SerializerOptions::new().float_policy(FloatPolicy::Truncate);
+        let f = truncate.to_bytes(&vec![1.5, f64::NAN]);
+        let g = truncate.to_bytes(&f64::NEG_INFINITY);
+        let h = truncate.to_bytes(&1e19);
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "float cannot be represented in a");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "float cannot be represented");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "float cannot be represented");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "float cannot be represented");
+        assert!(e.is_err());
+        assert_eq!(e.unwrap_err().to_string(), "float cannot be represented");
+        assert!(f.is_err());
+        assert_eq!(f.unwrap_err().to_string(), "float cannot be represented in [1]");
+        assert!(g.is_err());
+        assert_eq!(g.unwrap_err().to_string(), "float cannot be represented");
+        assert!(h.is_err());
+        assert_eq!(h.unwrap_err().to_string(), "float cannot be represented");
     }
 
     #[test]
@@ -409,6 +1202,50 @@ mod tests {
         assert_eq!(to_bytes(&o).unwrap().len(), 0);
     }
 
+    #[test]
+    fn test_option_entries() {
+        #[derive(Serialize)]
+        struct Test { a: Option<i64>, b: (), c: Option<Option<&'static str>>, d: Option<i64> }
+
+        let t = Test { a: None, b: (), c: Some(None), d: Some(1) };
+        let m = BTreeMap::from([("a", None), ("b", Some(2))]);
+
+        assert_eq!(to_bytes(&t).unwrap(), b"d1:di1ee");
+        assert_eq!(SerializerOptions::new().sort_keys(false).to_bytes(&t).unwrap(), b"d1:di1ee");
+        assert_eq!(to_bytes(&m).unwrap(), b"d1:bi2ee");
+        assert_eq!(to_bytes(&Test { a: None, b: (), c: None, d: None }).unwrap(), b"de");
+    }
+
+    #[test]
+    fn test_option_list() {
+        let v = vec![Some(1), None, Some(2)];
+        let options = SerializerOptions::new().none_policy(NonePolicy::Skip);
+
+        assert_eq!(options.to_bytes(&v).unwrap(), b"li1ei2ee");
+        assert_eq!(options.to_bytes(&((), 1)).unwrap(), b"li1ee");
+        assert_eq!(to_bytes(&vec![Some(1), Some(2)]).unwrap(), b"li1ei2ee");
+    }
+
+    #[test]
+    fn test_option_list_err() {
+        let a = to_bytes(&vec![Some(1), None, Some(2)]);
+        let b = to_bytes(&BTreeMap::from([("a", ((), 1))]));
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "unit and `None` cannot be represented inside a list in [1]");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "unit and `None` cannot be represented inside a list in a[0]");
+    }
+
+    #[test]
+    fn test_option_list_skip_err() {
+        let v = vec![None, Some(BTreeMap::from([(1, 2)]))];
+        let a = SerializerOptions::new().none_policy(NonePolicy::Skip).to_bytes(&v);
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "key must be a string in [1]");
+    }
+
     #[test]
     fn test_unit() {
         let u = ();
@@ -456,6 +1293,20 @@ mod tests {
         assert_eq!(to_bytes(&nv).unwrap(), b"d1:Ai69ee");
     }
 
+    #[test]
+    fn test_newtype_variant_err() {
+        #[derive(Serialize)]
+        enum Test { A(Option<u8>), B(()) }
+
+        let a = to_bytes(&Test::A(None));
+        let b = SerializerOptions::new().none_policy(NonePolicy::Skip).to_bytes(&Test::B(()));
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "unit and `None` cannot be represented as a variant value in A");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "unit and `None` cannot be represented as a variant value in B");
+    }
+
     #[test]
     fn test_seq() {
         let v = vec![1u8, 2, 3, 4, 5];
@@ -523,4 +1374,189 @@ mod tests {
 
         assert_eq!(to_bytes(&sv).unwrap(), b"d1:Ad1:ai12345e1:bl5:hello5:worldeee");
     }
+
+    #[test]
+    fn test_sorted_keys() {
+        #[derive(Serialize)]
+        struct Inner { z: u8, a: u8 }
+
+        #[derive(Serialize)]
+        struct Test { piece_length: u32, name: &'static str, inner: Inner }
+
+        let s = Test { piece_length: 16384, name: "test", inner: Inner { z: 1, a: 2 } };
+
+        assert_eq!(to_bytes(&s).unwrap(), b"d5:innerd1:ai2e1:zi1ee4:name4:test12:piece_lengthi16384ee");
+    }
+
+    #[test]
+    fn test_sorted_keys_raw_bytes() {
+        let mut m = HashMap::new();
+        m.insert("b", 1u8);
+        m.insert("a", 2);
+        m.insert("aa", 3);
+        m.insert("B", 4);
+
+        assert_eq!(to_bytes(&m).unwrap(), b"d1:Bi4e1:ai2e2:aai3e1:bi1ee");
+    }
+
+    #[test]
+    fn test_sorted_keys_err() {
+        #[derive(Serialize)]
+        struct Inner { a: u8 }
+
+        #[derive(Serialize)]
+        struct Test { a: u8, #[serde(flatten)] inner: Inner }
+
+        #[derive(Serialize)]
+        struct Outer { b: Test }
+
+        let e = to_bytes(&Outer { b: Test { a: 1, inner: Inner { a: 2 } } });
+
+        assert!(e.is_err());
+        assert_eq!(e.unwrap_err().to_string(), "duplicate dictionary key in b.a");
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        #[derive(Serialize)]
+        enum Test { A { z: u8, a: u8 } }
+
+        #[derive(Serialize)]
+        struct Outer { z: u8, a: Test }
+
+        let s = Outer { z: 1, a: Test::A { z: 2, a: 3 } };
+        let options = SerializerOptions::new().sort_keys(false);
+
+        assert_eq!(options.to_bytes(&s).unwrap(), b"d1:zi1e1:ad1:Ad1:zi2e1:ai3eeee");
+
+        let mut out = Vec::new();
+        SerializerOptions::new().to_writer(&s, &mut out).unwrap();
+        assert_eq!(out, b"d1:ad1:Ad1:ai3e1:zi2eee1:zi1ee");
+    }
+
+    #[test]
+    fn test_map_key_err() {
+        let mut m = BTreeMap::new();
+        m.insert(1u8, 2u8);
+
+        let e = to_bytes(&m);
+
+        assert!(e.is_err());
+        assert_eq!(e.unwrap_err().to_string(), "key must be a string");
+    }
+
+    #[test]
+    fn test_error_path() {
+        struct Failing;
+
+        impl Serialize for Failing {
+            fn serialize<S: serde::Serializer>(&self, _serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("cannot serialize"))
+            }
+        }
+
+        #[derive(Serialize)]
+        struct File {
+            length: u64,
+            path: Vec<Failing>,
+        }
+
+        let files = vec![("files", vec![File { length: 1, path: vec![] }, File { length: 2, path: vec![Failing] }])];
+        let a = to_bytes(&files);
+        let b = SerializerOptions::new().sort_keys(false).to_bytes(&BTreeMap::from([("info", BTreeMap::from([(1, 2)]))]));
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "cannot serialize in [0][1][1].path[0]");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "key must be a string in info");
+    }
+
+    #[test]
+    fn test_serializer_inner() {
+        let mut ser = Serializer::new(Vec::new());
+        1.serialize(&mut ser).unwrap();
+        ser.get_mut().push(b'\n');
+        "ab".serialize(&mut ser).unwrap();
+
+        assert_eq!(ser.get_ref().len(), 8);
+        assert_eq!(ser.into_inner(), b"i1e\n2:ab");
+    }
+
+    #[test]
+    fn test_serializer_adapters() {
+        #[derive(Serialize)]
+        struct Info {
+            name: &'static str,
+            length: Option<f64>,
+        }
+
+        let mut ser = Serializer::with_options(Vec::new(), SerializerOptions::new().float_policy(FloatPolicy::Round));
+        serde_path_to_error::serialize(&Info { name: "x", length: Some(1.6) }, &mut ser).unwrap();
+        assert_eq!(ser.into_inner(), b"d6:lengthi2e4:name1:xe");
+
+        let mut ser = Serializer::new(Vec::new());
+        let values: Vec<Box<dyn erased_serde::Serialize>> = vec![Box::new(3u8), Box::new("abc"), Box::new(vec![1, 2])];
+        for value in &values {
+            erased_serde::serialize(value.as_ref(), &mut ser).unwrap();
+        }
+        assert_eq!(ser.into_inner(), b"i3e3:abcli1ei2ee");
+    }
+
+    #[test]
+    fn test_serializer_adapters_err() {
+        #[derive(Serialize)]
+        struct Info {
+            files: Vec<Option<u8>>,
+        }
+
+        let mut ser = Serializer::new(Vec::new());
+        let a = serde_path_to_error::serialize(&Info { files: vec![Some(1), None] }, &mut ser);
+        let mut ser = Serializer::with_options(Vec::new(), SerializerOptions::new().float_policy(FloatPolicy::Reject));
+        let b = erased_serde::serialize(&1.5f64 as &dyn erased_serde::Serialize, &mut ser);
+
+        assert!(a.is_err());
+        // The element is rejected before it is handed to the adapter.
+        let a = a.unwrap_err();
+        assert_eq!(a.path().to_string(), "files");
+        assert_eq!(a.inner().to_string(), "unit and `None` cannot be represented inside a list in files[1]");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "float cannot be represented");
+    }
+
+    #[test]
+    fn test_serialized_size() {
+        #[derive(Serialize)]
+        struct Reply {
+            t: &'static str,
+            y: char,
+            r: HashMap<&'static str, Vec<u8>>,
+            ip: Option<[u8; 4]>,
+            v: Vec<i64>,
+        }
+
+        let reply = Reply {
+            t: "aa",
+            y: 'r',
+            r: HashMap::from([("id", vec![0xab; 20]), ("nodes", vec![1; 26]), ("token", vec![])]),
+            ip: None,
+            v: vec![-1, 0, i64::MIN, 123_456],
+        };
+        let floats = SerializerOptions::new().float_policy(FloatPolicy::Decimal);
+
+        assert_eq!(serialized_size(&reply).unwrap(), to_bytes(&reply).unwrap().len());
+        assert_eq!(serialized_size(&()).unwrap(), 0);
+        assert_eq!(serialized_size(&u128::MAX).unwrap(), 41);
+        assert_eq!(floats.serialized_size(&[0.1, 1e-7]).unwrap(), floats.to_bytes(&[0.1, 1e-7]).unwrap().len());
+    }
+
+    #[test]
+    fn test_serialized_size_err() {
+        let a = serialized_size(&BTreeMap::from([((), 1)]));
+        let b = serialized_size(&vec![vec![Some(1), None]]);
+
+        assert!(a.is_err());
+        assert_eq!(a.unwrap_err().to_string(), "key must be a string");
+        assert!(b.is_err());
+        assert_eq!(b.unwrap_err().to_string(), "unit and `None` cannot be represented inside a list in [0][1]");
+    }
 }
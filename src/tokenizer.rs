@@ -0,0 +1,362 @@
+use std::str;
+
+use super::de::{Deserializer, DeserializerOptions};
+use super::error::{Error, ErrorKind, Result};
+use super::read::SliceRead;
+
+/// Event produced by a `Tokenizer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// An integer fitting in an `i64`.
+    Int(i64),
+    /// An integer outside the range of `i64`, as its decimal digits with an
+    /// optional leading `-`.
+    BigInt(&'a str),
+    /// A byte string, borrowed from the input. Dictionary keys are byte
+    /// strings too.
+    Bytes(&'a [u8]),
+    ListStart,
+    DictStart,
+    /// End of the innermost list or dictionary.
+    End,
+}
+
+/// Pull parser producing the events of a Bencode document along with the
+/// offsets they start at, without building any value.
+///
+/// The document is validated as it is read, with the same rules and limits
+/// as the `Deserializer`: dictionary keys must be byte strings, integers must
+/// be canonical, and in strict mode keys must be sorted. Memory use does not
+/// depend on the size of the input, and `skip_value` jumps over a whole
+/// subtree without producing its events.
+///
+/// Once the document ends, trailing characters are reported as an error.
+/// Iteration stops after the first error.
+pub struct Tokenizer<'a> {
+    input: &'a [u8],
+    de: Deserializer<SliceRead<'a>>,
+    stack: Vec<Frame<'a>>,
+    done: bool,
+    failed: bool,
+}
+
+struct Frame<'a> {
+    dict: bool,
+    /// For dictionaries, whether the next token is a key.
+    expect_key: bool,
+    count: usize,
+    /// Last key of a dictionary, compared against the next one in strict
+    /// mode.
+    last_key: Option<&'a [u8]>,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Tokenizer::with_options(input, DeserializerOptions::default())
+    }
+
+    pub fn with_options(input: &'a [u8], options: DeserializerOptions) -> Self {
+        Tokenizer {
+            input,
+            de: Deserializer::with_options(input, options),
+            stack: Vec::new(),
+            done: false,
+            failed: false,
+        }
+    }
+
+    /// Number of bytes consumed so far.
+    pub fn byte_offset(&self) -> usize {
+        self.de.byte_offset()
+    }
+
+    /// Nesting depth of the current position: the number of lists and
+    /// dictionaries started and not yet ended.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Skips the next value, including everything nested in it, and returns
+    /// the offset it starts at. Right after a dictionary key, this skips the
+    /// value associated with it. Skipped values are checked like any other:
+    /// for being well formed, within the limits and, in strict mode, for key
+    /// order. Errors inside them carry their path from the skipped value.
+    pub fn skip_value(&mut self) -> Result<usize> {
+        let result = self.skip();
+        self.failed |= result.is_err();
+        result
+    }
+
+    fn skip(&mut self) -> Result<usize> {
+        if self.stack.last().is_some_and(|f| f.dict && f.expect_key) {
+            return Err(self.de.error_with_index(ErrorKind::ExpectedSomeValue, self.de.byte_offset()));
+        }
+
+        match self.next_event()? {
+            Some((offset, Event::ListStart)) | Some((offset, Event::DictStart)) => {
+                let frame = self.stack.last().expect("container was just started");
+                if frame.dict { self.de.ignore_dict()? } else { self.de.ignore_list()? }
+                self.close();
+                Ok(offset)
+            },
+            Some((offset, _)) => Ok(offset),
+            None => Err(Error::eof(self.de.byte_offset())),
+        }
+    }
+
+    fn next_event(&mut self) -> Result<Option<(usize, Event<'a>)>> {
+        if self.done {
+            self.de.end()?;
+            return Ok(None);
+        }
+
+        let offset = self.de.byte_offset();
+        let b = self.de.peek_byte()?;
+
+        match self.stack.last() {
+            Some(frame) if frame.dict && frame.expect_key => match b {
+                b'e' => self.end(offset),
+                b'0'..=b'9' => self.key(offset).map(Some),
+                b'l' | b'd' | b'i' => Err(self.de.error_with_index(ErrorKind::KeyMustBeAString, offset)),
+                _ => Err(self.de.error_with_index(ErrorKind::ExpectedEnd, offset)),
+            },
+            Some(frame) if !frame.dict => match b {
+                b'e' => self.end(offset),
+                b'l' | b'd' | b'i' | b'0'..=b'9' => {
+                    let max = self.de.options.max_list_len;
+                    let frame = self.stack.last_mut().expect("inside a list");
+                    self.de.count_element(&mut frame.count, max, ErrorKind::ListTooLong)?;
+                    self.value(b, offset).map(Some)
+                },
+                _ => Err(self.de.error_with_index(ErrorKind::ExpectedEnd, offset)),
+            },
+            _ => self.value(b, offset).map(Some),
+        }
+    }
+
+    fn key(&mut self, offset: usize) -> Result<(usize, Event<'a>)> {
+        let max = self.de.options.max_dict_len;
+        let strict = self.de.options.strict;
+        let frame = self.stack.last_mut().expect("inside a dictionary");
+        self.de.count_element(&mut frame.count, max, ErrorKind::DictTooLong)?;
+
+        let key = self.bytes()?;
+        let frame = self.stack.last_mut().expect("inside a dictionary");
+        if strict {
+            match frame.last_key {
+                Some(last) if key < last => return Err(Error::syntax(ErrorKind::UnsortedKeys, offset)),
+                Some(last) if key == last => return Err(Error::syntax(ErrorKind::DuplicateKey, offset)),
+                _ => {},
+            }
+        }
+        frame.last_key = Some(key);
+        frame.expect_key = false;
+
+        Ok((offset, Event::Bytes(key)))
+    }
+
+    fn value(&mut self, b: u8, offset: usize) -> Result<(usize, Event<'a>)> {
+        let event = match b {
+            b'i' => {
+                self.de.next_byte()?;
+                let (positive, n) = self.de.parse_magnitude(false, offset)?;
+                let int = match n {
+                    Some(n) if positive => i64::try_from(n).ok(),
+                    Some(n) => u64::try_from(n).ok().and_then(|n| 0i64.checked_sub_unsigned(n)),
+                    None => None,
+                };
+                match int {
+                    Some(i) => Event::Int(i),
+                    // Digits are ASCII.
+                    None => Event::BigInt(str::from_utf8(&self.input[offset + 1..self.de.byte_offset() - 1]).unwrap_or_default()),
+                }
+            },
+            b'0'..=b'9' => Event::Bytes(self.bytes()?),
+            b'l' | b'd' => {
+                self.de.next_byte()?;
+                self.de.enter_nested()?;
+                self.stack.push(Frame { dict: b == b'd', expect_key: true, count: 0, last_key: None });
+                return Ok((offset, if b == b'd' { Event::DictStart } else { Event::ListStart }));
+            },
+            _ => return Err(self.de.error_with_index(ErrorKind::ExpectedSomeValue, offset)),
+        };
+
+        self.after_value();
+        Ok((offset, event))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let (bytes, end) = self.de.parse_bytes_with_end()?;
+        let len = bytes.len();
+        Ok(&self.input[end - len..end])
+    }
+
+    fn end(&mut self, offset: usize) -> Result<Option<(usize, Event<'a>)>> {
+        self.de.next_byte()?;
+        self.close();
+        Ok(Some((offset, Event::End)))
+    }
+
+    /// Pops the innermost container, whose end was just read.
+    fn close(&mut self) {
+        self.stack.pop();
+        self.de.leave_nested();
+        self.after_value();
+    }
+
+    fn after_value(&mut self) {
+        match self.stack.last_mut() {
+            Some(frame) if frame.dict => frame.expect_key = true,
+            Some(_) => {},
+            None => self.done = true,
+        }
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<(usize, Event<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let result = self.next_event();
+        self.failed = result.is_err();
+        result.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{from_bytes, DeserializerOptions, RawBencode};
+    use super::{Event, Tokenizer};
+
+    const TORRENT: &[u8] = b"d8:announce3:url4:infod6:lengthi42e4:name1:x6:pieces2:abee";
+
+    fn events(input: &[u8]) -> Vec<(usize, Event<'_>)> {
+        Tokenizer::new(input).collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn test_events() {
+        assert_eq!(events(b"i-3e"), [(0, Event::Int(-3))]);
+        assert_eq!(events(b"le"), [(0, Event::ListStart), (1, Event::End)]);
+        assert_eq!(
+            events(TORRENT),
+            [
+                (0, Event::DictStart),
+                (1, Event::Bytes(b"announce")),
+                (11, Event::Bytes(b"url")),
+                (16, Event::Bytes(b"info")),
+                (22, Event::DictStart),
+                (23, Event::Bytes(b"length")),
+                (31, Event::Int(42)),
+                (35, Event::Bytes(b"name")),
+                (41, Event::Bytes(b"x")),
+                (44, Event::Bytes(b"pieces")),
+                (52, Event::Bytes(b"ab")),
+                (56, Event::End),
+                (57, Event::End),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_big_int() {
+        assert_eq!(
+            events(b"li9223372036854775807ei-9223372036854775808ei9223372036854775808ei-123456789012345678901234567890ee"),
+            [
+                (0, Event::ListStart),
+                (1, Event::Int(i64::MAX)),
+                (22, Event::Int(i64::MIN)),
+                (44, Event::BigInt("9223372036854775808")),
+                (65, Event::BigInt("-123456789012345678901234567890")),
+                (98, Event::End),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_skip_value() {
+        let mut tokenizer = Tokenizer::new(TORRENT);
+
+        assert_eq!(tokenizer.next().unwrap().unwrap(), (0, Event::DictStart));
+        assert_eq!(tokenizer.next().unwrap().unwrap(), (1, Event::Bytes(b"announce")));
+        assert_eq!(tokenizer.skip_value().unwrap(), 11);
+        assert_eq!(tokenizer.next().unwrap().unwrap(), (16, Event::Bytes(b"info")));
+        assert_eq!(tokenizer.skip_value().unwrap(), 22);
+        assert_eq!(tokenizer.depth(), 1);
+        assert_eq!(tokenizer.byte_offset(), 57);
+        assert_eq!(tokenizer.next().unwrap().unwrap(), (57, Event::End));
+        assert!(tokenizer.next().is_none());
+    }
+
+    #[test]
+    fn test_skip_value_err() {
+        let mut a = Tokenizer::new(b"d1:ai1ee");
+        a.next();
+        let mut b = Tokenizer::new(b"d1:ali1e");
+        b.next();
+        b.next();
+        let mut c = Tokenizer::with_options(b"d1:ad1:bi1e1:ai2eee", DeserializerOptions::new().strict(true));
+        c.next();
+        c.next();
+        let c = c.skip_value();
+        let mut d = Tokenizer::new(b"l1:ad1:bli1ei-0eeee");
+        d.next();
+        d.next();
+        let d = d.skip_value();
+
+        assert!(a.skip_value().is_err());
+        assert_eq!(a.skip_value().unwrap_err().to_string(), "expected value at index 1");
+        assert!(b.skip_value().is_err());
+        assert_eq!(b.skip_value().unwrap_err().to_string(), "EOF while parsing at index 8");
+        assert!(c.is_err());
+        assert_eq!(c.unwrap_err().to_string(), "dictionary keys are not sorted at index 11");
+        assert!(d.is_err());
+        assert_eq!(d.unwrap_err().to_string(), "`i-0e` is invalid at index 12 in b[1]");
+    }
+
+    #[test]
+    fn test_tokenizer_err() {
+        let inputs: &[&[u8]] = &[
+            b"d1:ai1e",
+            b"li1ei2ea",
+            b"di1ei2ee",
+            b"i01e",
+            b"i-0e",
+            b"02:ab",
+            b"3:ab",
+            b"d1:ae",
+            b"i1ei2e",
+            b"x",
+        ];
+
+        for input in inputs {
+            let expected = from_bytes::<RawBencode>(input).unwrap_err();
+            let err = Tokenizer::new(input).find_map(Result::err).unwrap();
+
+            // Only the deserializer tracks paths.
+            assert_eq!(err.index(), expected.index(), "{:?}", String::from_utf8_lossy(input));
+            assert_eq!(err.kind(), expected.kind(), "{:?}", String::from_utf8_lossy(input));
+        }
+    }
+
+    #[test]
+    fn test_tokenizer_options() {
+        let strict = DeserializerOptions::new().strict(true);
+        let limited = DeserializerOptions::new().max_list_len(1).recursion_limit(1);
+
+        let a = Tokenizer::with_options(b"d1:bi1e1:ai2ee", strict).find_map(Result::err);
+        let b = Tokenizer::with_options(b"li1ei2ee", limited).find_map(Result::err);
+        let c = Tokenizer::with_options(b"llee", limited).find_map(Result::err);
+
+        assert!(a.is_some());
+        assert_eq!(a.unwrap().to_string(), "dictionary keys are not sorted at index 7");
+        assert!(b.is_some());
+        assert_eq!(b.unwrap().to_string(), "list exceeds the size limit at index 4");
+        assert!(c.is_some());
+        assert_eq!(c.unwrap().to_string(), "recursion limit exceeded at index 1");
+    }
+}
@@ -0,0 +1,1207 @@
+use std::{
+    collections::{btree_map, BTreeMap},
+    fmt, str, vec,
+};
+
+use serde::{
+    de::{self, DeserializeSeed, IntoDeserializer, Unexpected, Visitor},
+    forward_to_deserialize_any,
+    ser::{self, Serialize},
+    Deserialize,
+};
+
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+
+#[cfg(feature = "bigint")]
+use super::bigint;
+use super::de::from_bytes;
+use super::error::{Error, ErrorKind, PathSegment, Result};
+use super::raw;
+use super::ser::to_bytes;
+
+/// Any valid Bencode document, whose shape does not need to be known ahead of
+/// time.
+///
+/// Dictionary keys are raw byte strings and are kept sorted, as required by
+/// the Bencode specification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Integer(i64),
+    /// An integer outside the range of `i64`. Values that fit in an `i64`
+    /// are always held by `Integer`, so that equal numbers compare equal.
+    #[cfg(feature = "bigint")]
+    BigInteger(BigInt),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Dict(BTreeMap<Vec<u8>, Value>),
+}
+
+impl Value {
+    pub fn as_integer(&self) -> Option<i64> {
+        match *self {
+            Value::Integer(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte string as a `&str` if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|b| str::from_utf8(b).ok())
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_list_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Value::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&BTreeMap<Vec<u8>, Value>> {
+        match self {
+            Value::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict_mut(&mut self) -> Option<&mut BTreeMap<Vec<u8>, Value>> {
+        match self {
+            Value::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    /// Looks up `key` if this value is a dictionary.
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&Value> {
+        self.as_dict().and_then(|d| d.get(key.as_ref()))
+    }
+
+    #[cold]
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            Value::Integer(i) => Unexpected::Signed(*i),
+            #[cfg(feature = "bigint")]
+            Value::BigInteger(_) => Unexpected::Other("big integer"),
+            Value::Bytes(b) => Unexpected::Bytes(b),
+            Value::List(_) => Unexpected::Seq,
+            Value::Dict(_) => Unexpected::Map,
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::Integer(i)
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl From<BigInt> for Value {
+    fn from(i: BigInt) -> Self {
+        match i64::try_from(&i) {
+            Ok(i) => Value::Integer(i),
+            Err(_) => Value::BigInteger(i),
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Bytes(s.as_bytes().to_vec())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Bytes(s.into_bytes())
+    }
+}
+
+impl From<&[u8]> for Value {
+    fn from(b: &[u8]) -> Self {
+        Value::Bytes(b.to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(b: Vec<u8>) -> Self {
+        Value::Bytes(b)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(l: Vec<Value>) -> Self {
+        Value::List(l)
+    }
+}
+
+impl From<BTreeMap<Vec<u8>, Value>> for Value {
+    fn from(d: BTreeMap<Vec<u8>, Value>) -> Self {
+        Value::Dict(d)
+    }
+}
+
+/// Converts any `T: Serialize` into a `Value`.
+///
+/// Struct fields and map entries holding `None` or `()` are left out, as
+/// Bencode has no way to represent them. Floats are rejected; encode them
+/// with a `FloatPolicy` through `SerializerOptions` instead.
+pub fn to_value<T>(value: &T) -> Result<Value>
+where
+    T: ?Sized + Serialize,
+{
+    value
+        .serialize(ValueSerializer)?
+        .ok_or_else(|| ser::Error::custom("unit and `None` cannot be represented as a value"))
+}
+
+/// Interprets a `Value` as an instance of type `T`.
+pub fn from_value<T>(value: Value) -> Result<T>
+where
+    T: de::DeserializeOwned,
+{
+    T::deserialize(value)
+}
+
+pub(crate) struct RawBytes<'a>(pub(crate) &'a [u8]);
+
+impl Serialize for RawBytes<'_> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self {
+            Value::Integer(i) => serializer.serialize_i64(*i),
+            #[cfg(feature = "bigint")]
+            Value::BigInteger(i) => bigint::serialize(i, serializer),
+            Value::Bytes(b) => serializer.serialize_bytes(b),
+            Value::List(l) => serializer.collect_seq(l),
+            Value::Dict(d) => serializer.collect_map(d.iter().map(|(k, v)| (RawBytes(k), v))),
+        }
+    }
+}
+
+struct KeyVisitor;
+
+impl<'de> Visitor<'de> for KeyVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a byte string key")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Self::Value, E> {
+        Ok(v.as_bytes().to_vec())
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> std::result::Result<Self::Value, E> {
+        Ok(v.into_bytes())
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<Self::Value, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> std::result::Result<Self::Value, E> {
+        Ok(v)
+    }
+}
+
+struct KeySeed;
+
+impl<'de> DeserializeSeed<'de> for KeySeed {
+    type Value = Vec<u8>;
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_byte_buf(KeyVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("any valid bencode value")
+            }
+
+            fn visit_bool<E: de::Error>(self, v: bool) -> std::result::Result<Value, E> {
+                Ok(Value::Integer(v as i64))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<Value, E> {
+                Ok(Value::Integer(v))
+            }
+
+            #[cfg(not(feature = "bigint"))]
+            fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<Value, E> {
+                i64::try_from(v)
+                    .map(Value::Integer)
+                    .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+            }
+
+            #[cfg(feature = "bigint")]
+            fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<Value, E> {
+                Ok(Value::from(BigInt::from(v)))
+            }
+
+            #[cfg(feature = "bigint")]
+            fn visit_i128<E: de::Error>(self, v: i128) -> std::result::Result<Value, E> {
+                Ok(Value::from(BigInt::from(v)))
+            }
+
+            #[cfg(feature = "bigint")]
+            fn visit_u128<E: de::Error>(self, v: u128) -> std::result::Result<Value, E> {
+                Ok(Value::from(BigInt::from(v)))
+            }
+
+            #[cfg(feature = "bigint")]
+            fn visit_newtype_struct<D>(self, deserializer: D) -> std::result::Result<Value, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                bigint::deserialize(deserializer).map(Value::from)
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Value, E> {
+                Ok(Value::Bytes(v.as_bytes().to_vec()))
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> std::result::Result<Value, E> {
+                Ok(Value::Bytes(v.into_bytes()))
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<Value, E> {
+                Ok(Value::Bytes(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> std::result::Result<Value, E> {
+                Ok(Value::Bytes(v))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Value, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(elem) = seq.next_element()? {
+                    list.push(elem);
+                }
+                Ok(Value::List(list))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> std::result::Result<Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut dict = BTreeMap::new();
+                while let Some(key) = map.next_key_seed(KeySeed)? {
+                    let value = map.next_value()?;
+                    dict.insert(key, value);
+                }
+                Ok(Value::Dict(dict))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Fixed-width integers are forwarded to `deserialize_any`, except for big
+/// integers wider than 128 bits which no primitive can hold.
+macro_rules! deserialize_fixed_width_integers {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                #[cfg(feature = "bigint")]
+                if let Value::BigInteger(ref i) = self {
+                    if i128::try_from(i).is_err() && u128::try_from(i).is_err() {
+                        return Err(Error::data(ErrorKind::IntegerOutOfRange));
+                    }
+                }
+
+                self.deserialize_any(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Integer(i) => visitor.visit_i64(i),
+            #[cfg(feature = "bigint")]
+            Value::BigInteger(i) => {
+                if let Ok(u) = u64::try_from(&i) {
+                    visitor.visit_u64(u)
+                } else if let Ok(i) = i128::try_from(&i) {
+                    visitor.visit_i128(i)
+                } else if let Ok(u) = u128::try_from(&i) {
+                    visitor.visit_u128(u)
+                } else {
+                    visitor.visit_newtype_struct(IntoDeserializer::<Error>::into_deserializer(i.to_string()))
+                }
+            },
+            Value::Bytes(b) => match String::from_utf8(b) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            },
+            Value::List(l) => visit_list(l, visitor),
+            Value::Dict(d) => visit_dict(d, visitor),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Integer(0) => visitor.visit_bool(false),
+            Value::Integer(1) => visitor.visit_bool(true),
+            other => Err(de::Error::invalid_type(other.unexpected(), &visitor)),
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Bytes(b) => visitor.visit_byte_buf(b),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    deserialize_fixed_width_integers! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        if name == raw::TOKEN {
+            return visitor.visit_byte_buf(to_bytes(&self)?);
+        }
+
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Bytes(variant) => visitor.visit_enum(EnumDeserializer { variant, value: None }),
+            Value::Dict(d) if d.len() == 1 => {
+                let (variant, value) = d.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer { variant, value: Some(value) })
+            },
+            other => Err(de::Error::invalid_type(other.unexpected(), &"a string or a dictionary with a single key")),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+fn visit_list<'de, V>(list: Vec<Value>, visitor: V) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    let len = list.len();
+    let mut seq = SeqDeserializer { iter: list.into_iter() };
+    let value = visitor.visit_seq(&mut seq)?;
+
+    if seq.iter.len() == 0 {
+        Ok(value)
+    } else {
+        Err(de::Error::invalid_length(len, &"fewer elements in list"))
+    }
+}
+
+fn visit_dict<'de, V>(dict: BTreeMap<Vec<u8>, Value>, visitor: V) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    let len = dict.len();
+    let mut map = MapDeserializer { iter: dict.into_iter(), value: None };
+    let value = visitor.visit_map(&mut map)?;
+
+    if map.iter.len() == 0 {
+        Ok(value)
+    } else {
+        Err(de::Error::invalid_length(len, &"fewer elements in dictionary"))
+    }
+}
+
+struct SeqDeserializer {
+    iter: vec::IntoIter<Value>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqDeserializer {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(value) => seed.deserialize(value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapDeserializer {
+    iter: btree_map::IntoIter<Vec<u8>, Value>,
+    value: Option<Value>,
+}
+
+impl<'de> de::MapAccess<'de> for MapDeserializer {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(KeyDeserializer(key)).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Dictionary keys are handed out as strings when they are valid UTF-8, so
+/// they can be matched against struct field names.
+struct KeyDeserializer(Vec<u8>);
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Value::Bytes(self.0).deserialize_any(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_byte_buf(self.0)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_byte_buf(self.0)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        Value::Bytes(self.0).deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+struct EnumDeserializer {
+    variant: Vec<u8>,
+    value: Option<Value>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(KeyDeserializer(self.variant))?;
+
+        Ok((variant, VariantDeserializer { value: self.value }))
+    }
+}
+
+struct VariantDeserializer {
+    value: Option<Value>,
+}
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.value {
+            None => Ok(()),
+            Some(value) => Err(de::Error::invalid_type(value.unexpected(), &"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.value {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &"newtype variant")),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Some(Value::List(l)) => visit_list(l, visitor),
+            Some(other) => Err(de::Error::invalid_type(other.unexpected(), &"tuple variant")),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &"tuple variant")),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Some(Value::Dict(d)) => visit_dict(d, visitor),
+            Some(other) => Err(de::Error::invalid_type(other.unexpected(), &"struct variant")),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &"struct variant")),
+        }
+    }
+}
+
+/// Serializer behind `to_value`. It yields `None` for values that have no
+/// Bencode representation (`()` and `None`), so that dictionaries can leave
+/// them out.
+struct ValueSerializer;
+
+#[cfg(not(feature = "bigint"))]
+fn int_value<T: TryInto<i64> + fmt::Display + Copy>(v: T) -> Result<Option<Value>> {
+    v.try_into()
+        .map(|i| Some(Value::Integer(i)))
+        .map_err(|_| ser::Error::custom(format_args!("integer {} does not fit in a value", v)))
+}
+
+#[cfg(feature = "bigint")]
+fn int_value<T: Into<BigInt>>(v: T) -> Result<Option<Value>> {
+    Ok(Some(Value::from(v.into())))
+}
+
+fn require(value: Option<Value>) -> Result<Value> {
+    value.ok_or_else(|| ser::Error::custom("unit and `None` cannot be represented inside a list"))
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeTupleVariant;
+    type SerializeMap = SerializeDict;
+    type SerializeStruct = SerializeDict;
+    type SerializeStructVariant = SerializeStructVariant;
+
+    fn serialize_bool(self, v: bool) -> Result<Option<Value>> {
+        Ok(Some(Value::Integer(v as i64)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Option<Value>> {
+        int_value(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Option<Value>> {
+        int_value(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Option<Value>> {
+        int_value(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Option<Value>> {
+        int_value(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Option<Value>> {
+        int_value(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Option<Value>> {
+        int_value(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Option<Value>> {
+        int_value(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Option<Value>> {
+        int_value(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Option<Value>> {
+        int_value(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Option<Value>> {
+        int_value(v)
+    }
+
+    // Like the default `FloatPolicy`, as there are no options to pick
+    // another one.
+    fn serialize_f32(self, _v: f32) -> Result<Option<Value>> {
+        Err(Error::data(ErrorKind::FloatNotRepresentable))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Option<Value>> {
+        Err(Error::data(ErrorKind::FloatNotRepresentable))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Option<Value>> {
+        Ok(Some(Value::from(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Option<Value>> {
+        Ok(Some(Value::from(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Option<Value>> {
+        Ok(Some(Value::from(v)))
+    }
+
+    fn serialize_none(self) -> Result<Option<Value>> {
+        Ok(None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Option<Value>>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Option<Value>> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Option<Value>> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Option<Value>> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Option<Value>>
+    where
+        T: ?Sized + Serialize,
+    {
+        match value.serialize(self)? {
+            Some(Value::Bytes(raw)) if name == raw::TOKEN => from_bytes(&raw).map(Some),
+            #[cfg(feature = "bigint")]
+            Some(Value::Bytes(digits)) if name == bigint::TOKEN => str::from_utf8(&digits)
+                .ok()
+                .and_then(|d| d.parse::<BigInt>().ok())
+                .map(|i| Some(Value::from(i)))
+                .ok_or_else(|| ser::Error::custom("invalid big integer")),
+            value => Ok(value),
+        }
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Option<Value>>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut dict = BTreeMap::new();
+        dict.insert(variant.as_bytes().to_vec(), to_value(value)?);
+        Ok(Some(Value::Dict(dict)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SerializeList { list: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(SerializeTupleVariant {
+            variant,
+            list: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(SerializeDict { dict: BTreeMap::new(), key: None })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(SerializeStructVariant { variant, dict: BTreeMap::new() })
+    }
+}
+
+struct SerializeList {
+    list: Vec<Value>,
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let index = self.list.len();
+        let value = value.serialize(ValueSerializer)
+            .and_then(require)
+            .map_err(|e| e.nested(PathSegment::Index(index), None))?;
+        self.list.push(value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        Ok(Some(Value::List(self.list)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct SerializeTupleVariant {
+    variant: &'static str,
+    list: Vec<Value>,
+}
+
+impl ser::SerializeTupleVariant for SerializeTupleVariant {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let index = self.list.len();
+        let value = value.serialize(ValueSerializer)
+            .and_then(require)
+            .map_err(|e| e.nested(PathSegment::Index(index), None))?;
+        self.list.push(value);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        let mut dict = BTreeMap::new();
+        dict.insert(self.variant.as_bytes().to_vec(), Value::List(self.list));
+        Ok(Some(Value::Dict(dict)))
+    }
+}
+
+struct SerializeDict {
+    dict: BTreeMap<Vec<u8>, Value>,
+    key: Option<Vec<u8>>,
+}
+
+impl ser::SerializeMap for SerializeDict {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        match key.serialize(ValueSerializer)? {
+            Some(Value::Bytes(key)) => {
+                self.key = Some(key);
+                Ok(())
+            },
+            _ => Err(ser::Error::custom("key must be a string")),
+        }
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self.key.take()
+            .ok_or_else(|| <Error as ser::Error>::custom("serialize_value called before serialize_key"))?;
+
+        let value = value.serialize(ValueSerializer)
+            .map_err(|e| e.nested(PathSegment::Key(key.clone()), None))?;
+        if let Some(value) = value {
+            self.dict.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        Ok(Some(Value::Dict(self.dict)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeDict {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = key.as_bytes().to_vec();
+        let value = value.serialize(ValueSerializer)
+            .map_err(|e| e.nested(PathSegment::Key(key.clone()), None))?;
+        if let Some(value) = value {
+            self.dict.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+struct SerializeStructVariant {
+    variant: &'static str,
+    dict: BTreeMap<Vec<u8>, Value>,
+}
+
+impl ser::SerializeStructVariant for SerializeStructVariant {
+    type Ok = Option<Value>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = key.as_bytes().to_vec();
+        let value = value.serialize(ValueSerializer)
+            .map_err(|e| e.nested(PathSegment::Key(key.clone()), None))?;
+        if let Some(value) = value {
+            self.dict.insert(key, value);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Option<Value>> {
+        let mut dict = BTreeMap::new();
+        dict.insert(self.variant.as_bytes().to_vec(), Value::Dict(self.dict));
+        Ok(Some(Value::Dict(dict)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{from_bytes, to_bytes};
+    use super::{from_value, to_value, Value};
+
+    fn dict(entries: Vec<(&str, Value)>) -> Value {
+        Value::Dict(entries.into_iter().map(|(k, v)| (k.as_bytes().to_vec(), v)).collect())
+    }
+
+    #[test]
+    fn test_de_value() {
+        let v: Value = from_bytes(b"d8:announce3:url4:infod6:lengthi42e5:filesl1:a1:beee").unwrap();
+
+        assert_eq!(v, dict(vec![
+            ("announce", Value::from("url")),
+            ("info", dict(vec![
+                ("length", Value::Integer(42)),
+                ("files", Value::List(vec![Value::from("a"), Value::from("b")])),
+            ])),
+        ]));
+        assert_eq!(v.get("info").and_then(|i| i.get("length")).and_then(Value::as_integer), Some(42));
+        assert_eq!(v.get("announce").and_then(Value::as_str), Some("url"));
+    }
+
+    #[test]
+    fn test_ser_value() {
+        let v = dict(vec![
+            ("b", Value::List(vec![Value::Integer(-1), Value::from(b"\xff\x00" as &[u8])])),
+            ("a", Value::Integer(7)),
+        ]);
+
+        assert_eq!(to_bytes(&v).unwrap(), b"d1:ai7e1:bli-1e2:\xff\x00ee");
+    }
+
+    #[test]
+    fn test_value_round_trip() {
+        let bytes = b"d3:bari-3e3:fool1:xi0eded3:keyi1eeee";
+        let v: Value = from_bytes(bytes).unwrap();
+
+        assert_eq!(to_bytes(&v).unwrap(), bytes);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Kind { Unit, Newtype(u8), Tuple(u8, String), Struct { x: i32 } }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Test {
+        name: String,
+        size: u32,
+        tags: Vec<String>,
+        comment: Option<String>,
+        kinds: Vec<Kind>,
+    }
+
+    #[test]
+    fn test_to_from_value() {
+        let t = Test {
+            name: "test".to_string(),
+            size: 42,
+            tags: vec!["a".to_string(), "b".to_string()],
+            comment: None,
+            kinds: vec![Kind::Unit, Kind::Newtype(1), Kind::Tuple(2, "c".to_string()), Kind::Struct { x: -3 }],
+        };
+
+        let v = to_value(&t).unwrap();
+
+        assert_eq!(v.get("comment"), None);
+        assert_eq!(v.get("size"), Some(&Value::Integer(42)));
+        assert_eq!(from_value::<Test>(v).unwrap(), t);
+    }
+
+    #[test]
+    fn test_to_value_err() {
+        let v = to_value(&vec![Some(1), None]);
+        let m = to_value(&BTreeMap::from([(1, 2)]));
+        let f = to_value(&1.7);
+        let n = to_value(&BTreeMap::from([("a", vec![(1, 2.5)])]));
+
+        assert!(v.is_err());
+        assert_eq!(v.unwrap_err().to_string(), "unit and `None` cannot be represented inside a list in [1]");
+        assert!(m.is_err());
+        assert_eq!(m.unwrap_err().to_string(), "key must be a string");
+        assert!(f.is_err());
+        assert_eq!(f.unwrap_err().to_string(), "float cannot be represented");
+        assert!(n.is_err());
+        assert_eq!(n.unwrap_err().to_string(), "float cannot be represented in a[0][1]");
+
+        #[cfg(not(feature = "bigint"))]
+        {
+            let u = to_value(&u64::MAX);
+
+            assert!(u.is_err());
+            assert_eq!(u.unwrap_err().to_string(), "integer 18446744073709551615 does not fit in a value");
+        }
+    }
+
+    #[test]
+    fn test_from_value_err() {
+        let i = from_value::<u8>(Value::Integer(-1));
+        let s = from_value::<String>(Value::Integer(1));
+
+        assert!(i.is_err());
+        assert_eq!(i.unwrap_err().to_string(), "invalid value: integer `-1`, expected u8");
+        assert!(s.is_err());
+        assert_eq!(s.unwrap_err().to_string(), "invalid type: integer `1`, expected a string");
+    }
+}
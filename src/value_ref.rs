@@ -0,0 +1,226 @@
+use std::{collections::BTreeMap, fmt, str};
+
+use serde::{
+    de::{self, DeserializeSeed, Unexpected, Visitor},
+    ser::{self, Serialize},
+    Deserialize,
+};
+
+use super::value::{RawBytes, Value};
+
+/// A Bencode document borrowing its byte strings and dictionary keys from the
+/// input it was parsed from.
+///
+/// Decoding a `ValueRef` never copies string contents, which makes it the
+/// cheapest way to inspect a document whose shape is not known ahead of time.
+/// It can only be deserialized from an input that outlives it, such as the
+/// slice given to `from_bytes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueRef<'de> {
+    Integer(i64),
+    Bytes(&'de [u8]),
+    List(Vec<ValueRef<'de>>),
+    Dict(BTreeMap<&'de [u8], ValueRef<'de>>),
+}
+
+impl<'de> ValueRef<'de> {
+    pub fn as_integer(&self) -> Option<i64> {
+        match *self {
+            ValueRef::Integer(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&'de [u8]> {
+        match *self {
+            ValueRef::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Returns the byte string as a `&str` if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&'de str> {
+        self.as_bytes().and_then(|b| str::from_utf8(b).ok())
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<ValueRef<'de>>> {
+        match self {
+            ValueRef::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&BTreeMap<&'de [u8], ValueRef<'de>>> {
+        match self {
+            ValueRef::Dict(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    /// Looks up `key` if this value is a dictionary.
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&ValueRef<'de>> {
+        self.as_dict().and_then(|d| d.get(key.as_ref()))
+    }
+}
+
+impl From<&ValueRef<'_>> for Value {
+    fn from(value: &ValueRef<'_>) -> Self {
+        match value {
+            ValueRef::Integer(i) => Value::Integer(*i),
+            ValueRef::Bytes(b) => Value::Bytes(b.to_vec()),
+            ValueRef::List(l) => Value::List(l.iter().map(Value::from).collect()),
+            ValueRef::Dict(d) => Value::Dict(d.iter().map(|(k, v)| (k.to_vec(), Value::from(v))).collect()),
+        }
+    }
+}
+
+impl From<ValueRef<'_>> for Value {
+    fn from(value: ValueRef<'_>) -> Self {
+        Value::from(&value)
+    }
+}
+
+impl Serialize for ValueRef<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        match self {
+            ValueRef::Integer(i) => serializer.serialize_i64(*i),
+            ValueRef::Bytes(b) => serializer.serialize_bytes(b),
+            ValueRef::List(l) => serializer.collect_seq(l),
+            ValueRef::Dict(d) => serializer.collect_map(d.iter().map(|(k, v)| (RawBytes(k), v))),
+        }
+    }
+}
+
+struct KeyVisitor;
+
+impl<'de> Visitor<'de> for KeyVisitor {
+    type Value = &'de [u8];
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a borrowed byte string key")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        Ok(v.as_bytes())
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+}
+
+struct KeySeed;
+
+impl<'de> DeserializeSeed<'de> for KeySeed {
+    type Value = &'de [u8];
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(KeyVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for ValueRef<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct ValueRefVisitor;
+
+        impl<'de> Visitor<'de> for ValueRefVisitor {
+            type Value = ValueRef<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("any valid bencode value borrowed from the input")
+            }
+
+            fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+                Ok(ValueRef::Integer(v as i64))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(ValueRef::Integer(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                i64::try_from(v)
+                    .map(ValueRef::Integer)
+                    .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+                Ok(ValueRef::Bytes(v.as_bytes()))
+            }
+
+            fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+                Ok(ValueRef::Bytes(v))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(elem) = seq.next_element()? {
+                    list.push(elem);
+                }
+                Ok(ValueRef::List(list))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut dict = BTreeMap::new();
+                while let Some(key) = map.next_key_seed(KeySeed)? {
+                    let value = map.next_value()?;
+                    dict.insert(key, value);
+                }
+                Ok(ValueRef::Dict(dict))
+            }
+        }
+
+        deserializer.deserialize_any(ValueRefVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{from_bytes, from_value, to_bytes, Value};
+    use super::ValueRef;
+
+    #[test]
+    fn test_de_value_ref() {
+        let input = b"d1:ali1ei-2ee1:b5:hello1:cd0:0:ee";
+        let v: ValueRef = from_bytes(input).unwrap();
+
+        let b = v.get("b").and_then(ValueRef::as_bytes).unwrap();
+        assert_eq!(b, b"hello");
+        assert!(input.as_ptr_range().contains(&b.as_ptr()));
+        assert_eq!(v.get("a").and_then(ValueRef::as_list).map(Vec::len), Some(2));
+        assert_eq!(v.get("c").and_then(|c| c.get("")), Some(&ValueRef::Bytes(b"")));
+    }
+
+    #[test]
+    fn test_value_ref_round_trip() {
+        let input = b"d3:bari-3e3:fool1:xi0eded3:keyi1eeee";
+        let v: ValueRef = from_bytes(input).unwrap();
+
+        assert_eq!(to_bytes(&v).unwrap(), input);
+    }
+
+    #[test]
+    fn test_value_ref_to_value() {
+        let input = b"d4:listli1e1:xe3:numi7ee";
+        let v: ValueRef = from_bytes(input).unwrap();
+        let owned = Value::from(&v);
+
+        assert_eq!(owned, from_bytes::<Value>(input).unwrap());
+        assert_eq!(from_value::<(i64, String)>(owned.get("list").unwrap().clone()).unwrap(), (1, "x".to_string()));
+    }
+}